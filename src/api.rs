@@ -1,11 +1,90 @@
 use serde::{Deserialize, Serialize};
-use crate::settings::Settings;
+use crate::cache;
+use crate::command_executor;
+use crate::history;
+use crate::postprocess::postprocess_command;
+use crate::settings::{Settings, DEFAULT_LMSTUDIO_URL};
+use crate::utf8::Utf8Decoder;
 use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use colored::*;
 
 // Set to true to enable debug output, false to disable
 const DEBUG: bool = false;
 
+const OLLAMA_KEEP_ALIVE: &str = "5m";
+
+// Messages API version sent with every Anthropic request
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+// Set once by --offline; every function that talks to a provider checks it first
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_offline() {
+    OFFLINE.store(true, Ordering::SeqCst);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+// The reply streamed so far by the request in flight, shown live in place of
+// the spinner; empty when nothing is streaming
+static STREAM_PREVIEW: Mutex<String> = Mutex::new(String::new());
+
+/// Text a streaming request has received so far (empty when none is running).
+pub fn stream_preview() -> String {
+    STREAM_PREVIEW.lock().map(|preview| preview.clone()).unwrap_or_default()
+}
+
+fn set_stream_preview(text: &str) {
+    if let Ok(mut preview) = STREAM_PREVIEW.lock() {
+        preview.clear();
+        preview.push_str(text);
+    }
+}
+
+// Warnings raised while the terminal is in raw mode, where a plain eprintln!
+// comes out stair-stepped; None while they print right away
+static HELD_WARNINGS: Mutex<Option<Vec<String>>> = Mutex::new(None);
+
+/// Holds back warnings (see `warn`) until `release_warnings`.
+pub fn hold_warnings() {
+    if let Ok(mut held) = HELD_WARNINGS.lock() {
+        held.get_or_insert_with(Vec::new);
+    }
+}
+
+/// Prints the warnings held back since `hold_warnings` and stops holding them.
+pub fn release_warnings() {
+    let held = HELD_WARNINGS.lock().ok().and_then(|mut held| held.take());
+    for message in held.unwrap_or_default() {
+        eprintln!("{}", message);
+    }
+}
+
+/// Prints a warning to stderr, or keeps it for `release_warnings` while held.
+pub fn warn(message: &str) {
+    let message = message.yellow().to_string();
+    if let Ok(mut held) = HELD_WARNINGS.lock() {
+        if let Some(held) = held.as_mut() {
+            held.push(message);
+            return;
+        }
+    }
+    eprintln!("{}", message);
+}
+
+fn ensure_online() -> Result<(), String> {
+    if is_offline() {
+        return Err("Offline mode (--offline): network requests are disabled.".to_string());
+    }
+    Ok(())
+}
+
 macro_rules! debug_print {
     ($($arg:tt)*) => {
         if DEBUG {
@@ -14,8 +93,9 @@ macro_rules! debug_print {
     };
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CommandSuggestion {
+    #[serde(default)]
     pub command: String,
     #[serde(default)]
     pub description: String,
@@ -25,23 +105,584 @@ pub struct CommandSuggestion {
     pub severity: String,
     #[serde(default)]
     pub severity_description: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub rationale: String, // one-line "why", only requested with inline_explanation
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub kind: String, // "action" or "info", only requested with help_exploration
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prerequisites: Vec<String>, // e.g. ["docker running", "jq"], only requested with check_prerequisites
+}
+
+/// How the model should structure explanations.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplainFormat {
+    #[default]
+    Prose,
+    Bullets,
+    Steps,
+}
+
+impl ExplainFormat {
+    // Appended to prompts that ask for an explanation; prose keeps the original wording
+    fn instruction(self) -> &'static str {
+        match self {
+            ExplainFormat::Prose => "",
+            ExplainFormat::Bullets => "\n\nWrite the explanation as short bullet points, one per line, each starting with \"- \".",
+            ExplainFormat::Steps => "\n\nWrite the explanation as a numbered step-by-step breakdown, one step per line (\"1. \", \"2. \", ...).",
+        }
+    }
+}
+
+/// How much detail an explanation goes into.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum ExplainVerbosity {
+    Short,
+    #[default]
+    Medium,
+    Long,
+}
+
+impl ExplainVerbosity {
+    pub const ALL: [ExplainVerbosity; 3] = [ExplainVerbosity::Short, ExplainVerbosity::Medium, ExplainVerbosity::Long];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ExplainVerbosity::Short => "short",
+            ExplainVerbosity::Medium => "medium",
+            ExplainVerbosity::Long => "long",
+        }
+    }
+
+    fn instruction(self) -> &'static str {
+        match self {
+            ExplainVerbosity::Short => "Explain it in one or two sentences.",
+            ExplainVerbosity::Medium => "Explain what it does and why, in a short paragraph.",
+            ExplainVerbosity::Long => "Explain it in depth: every part and flag, what it changes, pitfalls and useful variations.",
+        }
+    }
+}
+
+// Roughly 4k tokens; for logs the end is usually what matters
+const MAX_ATTACHMENT_BYTES: usize = 16 * 1024;
+
+// Below this an attachment is more confusing than helpful, so it's dropped instead
+const MIN_ATTACHMENT_BYTES: usize = 512;
+
+// Rough estimate, good enough for English text and code
+const BYTES_PER_TOKEN: usize = 4;
+
+// Left for the fixed instructions, the system prompt and the reply
+const PROMPT_RESERVE_TOKENS: usize = 1536;
+
+/// Tokens the context block may use: `num_ctx` from extra_params (Ollama only)
+/// or the context_window setting, else what the provider serves by default
+/// (Ollama silently cuts prompts beyond its 4096-token default).
+fn context_budget_bytes(settings: &Settings) -> usize {
+    let window = settings
+        .extra_params
+        .get("num_ctx")
+        .filter(|_| settings.provider == "ollama")
+        .and_then(|value| value.as_u64())
+        .map(|tokens| tokens as usize)
+        .or(settings.context_window)
+        .unwrap_or(match settings.provider.as_str() {
+            "cerebras" => 8192,
+            "openai" => 16384,
+            "anthropic" => 32768,
+            "groq" => 8192,
+            "gemini" => 32768,
+            "mistral" => 32768,
+            _ => 4096,
+        });
+    window.saturating_sub(PROMPT_RESERVE_TOKENS) * BYTES_PER_TOKEN
+}
+
+/// A file attached with `--context`, already truncated to fit the prompt.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub name: String,
+    pub content: String,
+}
+
+impl Attachment {
+    pub fn from_file(path: &std::path::Path) -> Result<Attachment, String> {
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("Failed to read context file {}: {}", path.display(), e))?;
+        let text = String::from_utf8_lossy(&bytes);
+
+        let content = if text.len() > MAX_ATTACHMENT_BYTES {
+            let start = text.ceil_char_boundary(text.len() - MAX_ATTACHMENT_BYTES);
+            format!(
+                "[truncated: showing the last {} of {} bytes]\n{}",
+                text.len() - start,
+                text.len(),
+                &text[start..]
+            )
+        } else {
+            text.into_owned()
+        };
+
+        Ok(Attachment {
+            name: path.display().to_string(),
+            content,
+        })
+    }
+}
+
+/// Extra, per-invocation context that gets folded into the prompt.
+#[derive(Debug, Clone, Default)]
+pub struct PromptContext {
+    pub cwd: Option<PathBuf>,
+    pub explain_format: Option<ExplainFormat>,
+    pub attachment: Option<Attachment>,
+}
+
+impl PromptContext {
+    /// The context block, trimmed to fit the model's context window. `alongside`
+    /// is the size in bytes of the rest of the variable prompt text (question,
+    /// command output, ...). Sections go least important first: the attached
+    /// file is cut down to its tail, then dropped; a warning says what went.
+    fn render(&self, settings: &Settings, alongside: usize) -> String {
+        let cwd = self
+            .cwd
+            .as_ref()
+            .map(|cwd| format!("\n\nThe command will be run from this working directory: {}", cwd.display()))
+            .unwrap_or_default();
+
+        let Some(attachment) = &self.attachment else {
+            return cwd;
+        };
+        let content = attachment.content.trim_end();
+        let header = format!("\n\nThe user attached this file as context ({}):\n<<<BEGIN CONTEXT\n", attachment.name);
+        let footer = "\nEND CONTEXT>>>";
+
+        let budget = context_budget_bytes(settings).saturating_sub(alongside + cwd.len() + header.len() + footer.len());
+        let content = if content.len() <= budget {
+            content.to_string()
+        } else if budget < MIN_ATTACHMENT_BYTES {
+            warn(&format!("⚠️  Dropped {} from the prompt: it doesn't fit the model's context window.", attachment.name));
+            return cwd;
+        } else {
+            let start = content.ceil_char_boundary(content.len() - budget);
+            warn(&format!("⚠️  Trimmed {} to its last {} bytes to fit the model's context window.", attachment.name, content.len() - start));
+            format!("[truncated to fit the context window]\n{}", &content[start..])
+        };
+
+        format!("{}{}{}{}", cwd, header, content, footer)
+    }
+
+    // --explain-format wins over the explanation_format setting
+    pub fn effective_explain_format(&self, settings: &Settings) -> ExplainFormat {
+        self.explain_format.or(settings.explanation_format).unwrap_or_default()
+    }
+
+    fn explanation_instruction(&self, settings: &Settings) -> &'static str {
+        self.effective_explain_format(settings).instruction()
+    }
+}
+
+pub async fn get_command_suggestion(question: &str, context: &PromptContext) -> Result<CommandSuggestion, String> {
+    let mut settings = Settings::load()?;
+
+    debug_print!("Question (normalized): {}", cache::normalize_question(question));
+
+    if is_offline() {
+        return offline_suggestion(question, context, &settings);
+    }
+
+    // An attached file changes the answer but isn't part of the cache key
+    let use_cache = settings.use_cache && context.attachment.is_none();
+
+    if use_cache {
+        if let Some(cached) = cache::get(question, context, &settings) {
+            debug_print!("✅ Cache hit");
+            return Ok(cached);
+        }
+    }
+
+    // Only alter what the model sees when the user opted in
+    let prompt_question = if settings.normalize_prompt {
+        cache::normalize_question(question)
+    } else {
+        question.to_string()
+    };
+
+    let mut suggestion = match suggestion_from_provider(&prompt_question, context, &settings).await {
+        Err(ApiError::Unreachable(e)) => suggestion_from_fallbacks(&prompt_question, context, &mut settings)
+            .await
+            .ok_or(e)?,
+        result => result?,
+    };
+    reconcile_fields(&mut suggestion, &settings, !is_offline()).await;
+    suggestion.command = postprocess_command(&suggestion.command, &settings);
+
+    let is_command = suggestion.command != "ERROR" && suggestion.command != "no command returned";
+    if use_cache && is_command {
+        if let Err(e) = cache::put(question, context, &settings, &suggestion) {
+            debug_print!("⚠️  Failed to update cache: {}", e);
+        }
+    }
+
+    Ok(suggestion)
+}
+
+async fn suggestion_from_provider(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    match settings.provider.as_str() {
+        "ollama" => get_command_from_ollama(question, context, settings).await,
+        "cerebras" => get_command_from_cerebras(question, context, settings).await,
+        "openai" => get_command_from_openai(question, context, settings).await,
+        "anthropic" => get_command_from_anthropic(question, context, settings).await,
+        "groq" => get_command_from_groq(question, context, settings).await,
+        "gemini" => get_command_from_gemini(question, context, settings).await,
+        "mistral" => get_command_from_mistral(question, context, settings).await,
+        "lmstudio" => get_command_from_lmstudio(question, context, settings).await,
+        _ => Err(ApiError::from("Invalid provider in settings")),
+    }
+}
+
+// Tries `fallback_providers` in order once the primary couldn't be reached.
+// On success `settings.provider` is the one that answered, so the answer is
+// cached under its model. None when every fallback failed too.
+async fn suggestion_from_fallbacks(question: &str, context: &PromptContext, settings: &mut Settings) -> Option<CommandSuggestion> {
+    let primary = settings.provider.clone();
+    for fallback in settings.fallback_providers.clone() {
+        if fallback == primary {
+            continue;
+        }
+        // The primary the first time round, then the fallback before this one
+        warn(&format!("⚠️  {} failed, trying fallback: {}", settings.provider, fallback));
+        settings.provider = fallback;
+        match suggestion_from_provider(question, context, settings).await {
+            Ok(suggestion) => return Some(suggestion),
+            Err(e) => warn(&format!("⚠️  {}", e)),
+        }
+    }
+    None
 }
 
-pub async fn get_command_suggestion(question: &str) -> Result<CommandSuggestion, String> {
+/// Why a provider request failed. Only `Unreachable`, a request that got no
+/// answer at all (refused, unreachable, timed out), sends the question on to
+/// `fallback_providers`; an error status or a reply that didn't parse is `Failed`.
+#[derive(Debug)]
+enum ApiError {
+    Unreachable(String),
+    Failed(String),
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiError::Unreachable(message) | ApiError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        ApiError::Failed(message)
+    }
+}
+
+impl From<&str> for ApiError {
+    fn from(message: &str) -> Self {
+        ApiError::Failed(message.to_string())
+    }
+}
+
+impl From<ApiError> for String {
+    fn from(error: ApiError) -> Self {
+        error.to_string()
+    }
+}
+
+/// `--raw-response`: sends the same request as a suggestion and returns the
+/// model's reply as-is, before any JSON parsing. Never uses the cache.
+pub async fn get_raw_response(question: &str, context: &PromptContext) -> Result<String, String> {
     let settings = Settings::load()?;
 
+    let prompt_question = if settings.normalize_prompt {
+        cache::normalize_question(question)
+    } else {
+        question.to_string()
+    };
+
+    let reply = match settings.provider.as_str() {
+        "ollama" => {
+            let prompt = two_pass_command_prompt(&prompt_question, context, &settings);
+            ollama_generate(&prompt, system_prompt(&settings), &settings, 120, true, false).await
+        }
+        "anthropic" => {
+            let prompt = two_pass_command_prompt(&prompt_question, context, &settings);
+            anthropic_messages(&prompt, system_prompt(&settings), &settings, 500).await
+        }
+        "mistral" => {
+            let prompt = two_pass_command_prompt(&prompt_question, context, &settings);
+            mistral_chat(&prompt, system_prompt(&settings), &settings, 500).await
+        }
+        "cerebras" => {
+            let prompt = chat_command_prompt(&prompt_question, context, &settings);
+            cerebras_chat(&prompt, system_prompt(&settings), &settings, 500).await
+        }
+        "openai" => {
+            let prompt = chat_command_prompt(&prompt_question, context, &settings);
+            openai_chat(&prompt, system_prompt(&settings), &settings, 500).await
+        }
+        "groq" => {
+            let prompt = chat_command_prompt(&prompt_question, context, &settings);
+            groq_chat(&prompt, system_prompt(&settings), &settings, 500).await
+        }
+        "lmstudio" => {
+            let prompt = chat_command_prompt(&prompt_question, context, &settings);
+            lmstudio_chat(&prompt, system_prompt(&settings), &settings, 500).await
+        }
+        "gemini" => {
+            let prompt = chat_command_prompt(&prompt_question, context, &settings);
+            gemini_generate(&prompt, system_prompt(&settings), &settings, 500).await.map(|reply| reply.text)
+        }
+        _ => Err(ApiError::from("Invalid provider in settings")),
+    };
+    reply.map_err(String::from)
+}
+
+// --offline: answer from the cache, then from history, never from the network
+fn offline_suggestion(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, String> {
+    if let Some(cached) = cache::get(question, context, settings) {
+        return Ok(cached);
+    }
+
+    match history::find_question(question)? {
+        Some(entry) => Ok(CommandSuggestion {
+            command: entry.command,
+            description: entry.description,
+            severity: entry.severity,
+            ..Default::default()
+        }),
+        None => Err("Offline mode (--offline): no cached answer or history entry matches this question.".to_string()),
+    }
+}
+
+/// For --compare: asks one specific model, bypassing the cache. `model_spec` is a
+/// model name for the configured provider, or `<provider>/<model>` for ollama,
+/// cerebras, openai, anthropic, groq, gemini, mistral or lmstudio.
+pub async fn get_command_suggestion_from(question: &str, context: &PromptContext, model_spec: &str) -> Result<CommandSuggestion, String> {
+    let mut settings = Settings::load()?;
+
+    let (provider, model) = match model_spec.split_once('/') {
+        Some((provider @ ("ollama" | "cerebras" | "openai" | "anthropic" | "groq" | "gemini" | "mistral" | "lmstudio"), model)) => (provider.to_string(), model),
+        _ => (settings.provider.clone(), model_spec),
+    };
+    settings.provider = provider;
+    match settings.provider.as_str() {
+        "openai" => settings.openai_model = Some(model.to_string()),
+        "anthropic" => settings.anthropic_model = Some(model.to_string()),
+        "groq" => settings.groq_model = Some(model.to_string()),
+        "gemini" => settings.gemini_model = Some(model.to_string()),
+        "cerebras" => settings.cerebras_model = Some(model.to_string()),
+        "mistral" => settings.mistral_model = Some(model.to_string()),
+        "lmstudio" => settings.lmstudio_model = Some(model.to_string()),
+        _ => settings.ollama_model = Some(model.to_string()),
+    }
+
+    let mut suggestion = suggestion_from_provider(question, context, &settings).await?;
+    reconcile_fields(&mut suggestion, &settings, !is_offline()).await;
+    suggestion.command = postprocess_command(&suggestion.command, &settings);
+    Ok(suggestion)
+}
+
+/// How the shell is named in prompts.
+pub fn shell_type() -> &'static str {
+    match (command_executor::shell_program(), env::consts::OS) {
+        ("powershell", _) => "PowerShell",
+        ("sh", "macos") => "shell",
+        ("sh", "freebsd") => "FreeBSD sh",
+        ("sh", "netbsd") => "NetBSD sh",
+        ("ksh", "openbsd") => "OpenBSD ksh",
+        ("sh", "dragonfly") => "DragonFly BSD sh",
+        ("sh", _) => "POSIX sh",
+        (shell, _) => shell,
+    }
+}
+
+const RATIONALE_FIELD: &str = "\"rationale\": \"one short sentence on why this command fits\"";
+
+const KIND_FIELD: &str = "\"kind\": \"action|info\"";
+
+const PREREQUISITES_FIELD: &str = "\"prerequisites\": [\"program that must be installed first, followed by ' running' if its service must be up; empty if none\"]";
+
+const KIND_HINT: &str = r#"
+
+Set "kind" to "info" when the question asks about a command's options or usage (e.g. "what are the options for tar") and to "action" when it asks to get something done. For "info", suggest a read-only exploration command such as `<program> --help`, `man <program>` or `<program> -h` instead of guessing at flags."#;
+
+// help_exploration: the intent hint goes after the prompt, template or not
+fn with_kind_hint(mut prompt: String, settings: &Settings) -> String {
+    if settings.help_exploration {
+        prompt.push_str(KIND_HINT);
+    }
+    prompt
+}
+
+// An "info" answer only shows documentation, so it's safe unless the model said otherwise
+fn apply_kind_defaults(parsed: &mut CommandSuggestion) {
+    if parsed.kind == "info" && parsed.severity.is_empty() {
+        parsed.severity = "safe".to_string();
+        parsed.severity_description = "Read-only: shows help or documentation".to_string();
+    }
+}
+
+// Small models tend to wrap JSON in prose or markdown unless told very explicitly not to
+const STRICT_JSON_TEMPLATE: &str = r#"You are a command line expert.
+Task: suggest ONE {shell} command for: {question}{context}
+
+Output rules:
+- Output a single JSON object and nothing else.
+- No markdown, no code fences, no comments, no text before or after the JSON.
+- Use exactly these keys:
+{
+    {fields}
+}
+
+If the request is not a task, set "command" to "no command returned"."#;
+
+/// Built-in templates, keyed by model name prefix.
+const BUILTIN_PROMPT_TEMPLATES: &[(&str, &str)] = &[
+    ("llama3.2", STRICT_JSON_TEMPLATE),
+    ("phi", STRICT_JSON_TEMPLATE),
+    ("gemma", STRICT_JSON_TEMPLATE),
+    ("qwen2.5:0.5b", STRICT_JSON_TEMPLATE),
+    ("qwen2.5:1.5b", STRICT_JSON_TEMPLATE),
+    ("tinyllama", STRICT_JSON_TEMPLATE),
+    // Mistral models follow a strict JSON-only instruction very reliably
+    ("mistral", STRICT_JSON_TEMPLATE),
+    ("codestral", STRICT_JSON_TEMPLATE),
+];
+
+/// Finds the prompt template for the configured model: user templates from
+/// `prompt_templates` first, then the built-ins. Keys match the model name
+/// exactly or as a prefix, longest key wins.
+fn prompt_template_for(settings: &Settings) -> Option<&str> {
+    let model = settings.model()?;
+
+    let user_templates = settings
+        .prompt_templates
+        .iter()
+        .map(|(key, template)| (key.as_str(), template.as_str()));
+
+    best_template_match(model, user_templates)
+        .or_else(|| best_template_match(model, BUILTIN_PROMPT_TEMPLATES.iter().copied()))
+}
+
+fn best_template_match<'a>(model: &str, candidates: impl Iterator<Item = (&'a str, &'a str)>) -> Option<&'a str> {
+    candidates
+        .filter(|(key, _)| model.starts_with(key))
+        .max_by_key(|(key, _)| key.len())
+        .map(|(_, template)| template)
+}
+
+/// Fills the `{shell}`, `{question}`, `{context}` and `{fields}` placeholders.
+fn render_prompt_template(template: &str, shell: &str, question: &str, context: &str, fields: &str) -> String {
+    template
+        .replace("{shell}", shell)
+        .replace("{context}", context)
+        .replace("{fields}", fields)
+        // last, so a question containing a placeholder isn't expanded
+        .replace("{question}", question)
+}
+
+/// Parses a JSON value out of model output, tolerating markdown fences and
+/// surrounding chatter. `open`/`close` are the delimiters of the expected value.
+fn extract_json<T: serde::de::DeserializeOwned>(content: &str, open: char, close: char) -> Result<T, serde_json::Error> {
+    let result = serde_json::from_str(content);
+    if result.is_ok() {
+        return result;
+    }
+
+    debug_print!("⚠️  First parse attempt failed, trying to extract JSON...");
+
+    // Try to remove markdown code block formatting (```json ... ```)
+    let mut clean_content = content.trim().to_string();
+    if clean_content.starts_with("```") {
+        // Remove opening ```json or ```
+        if let Some(start_idx) = clean_content.find('\n') {
+            clean_content = clean_content[start_idx + 1..].to_string();
+        }
+    }
+    if clean_content.ends_with("```") {
+        clean_content.truncate(clean_content.len() - 3);
+    }
+
+    // Now try to extract JSON
+    if let Some(start) = clean_content.find(open) {
+        if let Some(end) = clean_content.rfind(close) {
+            if end > start {
+                let extracted = &clean_content[start..=end];
+                debug_print!("Extracted JSON (after markdown cleanup):");
+                debug_print!("{}", extracted);
+                return serde_json::from_str(extracted);
+            }
+        }
+    }
+
+    result
+}
+
+const DEFAULT_SYSTEM_PROMPT: &str = "You are a command suggestion tool. Suggest commands or 'no command returned'. \
+Always respond with JSON only: no markdown, no code fences, no text outside the JSON.";
+
+// For replies that are meant to be read, not parsed
+const PLAIN_TEXT_SYSTEM_PROMPT: &str = "You are a command line expert. Answer in plain text.";
+
+/// The `system_prompt` setting, or the JSON-only default.
+fn system_prompt(settings: &Settings) -> &str {
+    settings
+        .system_prompt
+        .as_deref()
+        .filter(|prompt| !prompt.trim().is_empty())
+        .unwrap_or(DEFAULT_SYSTEM_PROMPT)
+}
+
+/// Sends a single prompt to the configured provider and returns the raw text reply.
+async fn complete(prompt: &str, settings: &Settings) -> Result<String, ApiError> {
+    complete_with_system(prompt, system_prompt(settings), settings).await
+}
+
+async fn complete_with_system(prompt: &str, system: &str, settings: &Settings) -> Result<String, ApiError> {
     match settings.provider.as_str() {
-        "ollama" => get_command_from_ollama(question, &settings).await,
-        "cerebras" => get_command_from_cerebras(question, &settings).await,
-        _ => Err("Invalid provider in settings".to_string()),
+        "ollama" => ollama_generate(prompt, system, settings, 120, false, false).await,
+        "cerebras" => cerebras_chat(prompt, system, settings, 1000).await,
+        "openai" => openai_chat(prompt, system, settings, 1000).await,
+        "anthropic" => anthropic_messages(prompt, system, settings, 1000).await,
+        "groq" => groq_chat(prompt, system, settings, 1000).await,
+        "gemini" => gemini_generate(prompt, system, settings, 1000).await.map(|reply| reply.text),
+        "mistral" => mistral_chat(prompt, system, settings, 1000).await,
+        "lmstudio" => lmstudio_chat(prompt, system, settings, 1000).await,
+        _ => Err(ApiError::from("Invalid provider in settings")),
     }
 }
 
-async fn get_command_from_ollama(question: &str, settings: &Settings) -> Result<CommandSuggestion, String> {
+/// Models whose reasoning would be squeezed into the JSON grammar, so
+/// `ollama_json_format` is off for them unless set explicitly.
+const NO_JSON_FORMAT_MODELS: &[&str] = &["deepseek-r1", "qwq"];
+
+fn ollama_json_format(settings: &Settings) -> bool {
+    settings.ollama_json_format.unwrap_or_else(|| {
+        let model = settings.ollama_model.as_deref().unwrap_or_default();
+        !NO_JSON_FORMAT_MODELS.iter().any(|prefix| model.starts_with(prefix))
+    })
+}
+
+/// `system` goes in `/api/generate`'s own `system` field, which replaces the
+/// system prompt of the model's template for this request.
+/// `json` marks prompts that expect a JSON object back; with `ollama_json_format`
+/// Ollama then constrains the reply to valid JSON.
+/// `stream` reads the reply as Ollama generates it (see `read_ollama_stream`).
+/// `timeout_secs` covers the whole response, not just the first byte.
+async fn ollama_generate(prompt: &str, system: &str, settings: &Settings, timeout_secs: u64, json: bool, stream: bool) -> Result<String, ApiError> {
+    ensure_online()?;
+
     let base_url = settings
         .ollama_base_url
-        .as_ref()
-        .map(|s| s.as_str())
+        .as_deref()
         .unwrap_or("http://localhost:11434");
 
     let model = settings
@@ -49,96 +690,76 @@ async fn get_command_from_ollama(question: &str, settings: &Settings) -> Result<
         .as_ref()
         .ok_or("Ollama model not configured")?;
 
-    let os = env::consts::OS;
-    let shell_type = match os {
-        "windows" => "PowerShell",
-        "linux" => "bash",
-        "macos" => "shell",
-        _ => "shell",
-    };
-
     let client = reqwest::Client::new();
     let url = format!("{}/api/generate", base_url);
 
-    // Build the JSON response format based on output settings
-    let mut json_fields = vec![];
-    if settings.output_settings.show_command {
-        json_fields.push("\"command\": \"exact command\"");
-    }
-    if settings.output_settings.show_description {
-        json_fields.push("\"description\": \"brief desc\"");
-    }
-    if settings.output_settings.show_severity {
-        json_fields.push("\"severity\": \"safe|warning|dangerous\"");
-        json_fields.push("\"severity_description\": \"risk\"");
-    }
-    
-    let json_format = json_fields.join(",\n    ");
-
-    // First call: Get command and description only
-    let prompt = format!(
-        r#"Suggest the best {} command for: {}
-
-Respond with ONLY valid JSON (no markdown, no extra text):
-{{
-    {}
-}}
-
-If not a task, use "no command returned" for command."#,
-        shell_type, question, json_format
-    );
-
-    let request_body = serde_json::json!({
+    let mut request_body = serde_json::json!({
         "model": model,
+        "system": system,
         "prompt": prompt,
         "temperature": 0.3,
-        "stream": false,
-        "keep_alive": "5m"
+        "stream": stream,
+        "keep_alive": OLLAMA_KEEP_ALIVE
     });
+    // Only sent when set; older Ollama versions and non-reasoning models reject it
+    if let Some(think) = settings.ollama_think {
+        request_body["think"] = serde_json::Value::Bool(think);
+    }
+    if json && ollama_json_format(settings) {
+        request_body["format"] = serde_json::Value::from("json");
+    }
+    if !settings.extra_params.is_empty() {
+        request_body["options"] = serde_json::Value::Object(settings.extra_params.clone());
+    }
 
-    debug_print!("🔍 [OLLAMA DEBUG - FIRST REQUEST]");
+    debug_print!("🔍 [OLLAMA DEBUG - REQUEST]");
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!("URL: {}", url);
     debug_print!("Model: {}", model);
     debug_print!("Base URL: {}", base_url);
-    debug_print!("Timeout: 120 seconds");
-    debug_print!("Output Settings:");
-    debug_print!("  show_command: {}", settings.output_settings.show_command);
-    debug_print!("  show_description: {}", settings.output_settings.show_description);
-    debug_print!("  show_severity: {}", settings.output_settings.show_severity);
-    debug_print!("  show_explanation: {}", settings.output_settings.show_explanation);
+    debug_print!("Timeout: {} seconds", timeout_secs);
     debug_print!("Request Body:");
     debug_print!("{}", serde_json::to_string_pretty(&request_body).unwrap_or_default());
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!();
 
-    let response = match tokio::time::timeout(
-        std::time::Duration::from_secs(120),
-        client.post(&url).json(&request_body).send(),
-    )
-    .await
-    {
-        Ok(Ok(resp)) => resp,
-        Ok(Err(e)) => {
-            eprintln!("{} {}", "❌ Connection Error:".red().bold(), e);
-            return Err(format!("❌ Ollama connection failed: {}. Make sure Ollama is running on {}", e, base_url));
+    let request = async {
+        let response = client
+            .post(&url)
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| ApiError::Unreachable(format!("❌ Ollama connection failed: {}. Make sure Ollama is running on {}", e, base_url)))?;
+
+        if stream && response.status().is_success() {
+            return Ok(read_ollama_stream(response).await?);
+        }
+
+        let status = response.status();
+        let response_text = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read Ollama response: {}", e))?;
+
+        if !status.is_success() {
+            return Err(ApiError::Failed(http_status_error("Ollama", status, &response_text)));
         }
+        Ok(response_text)
+    };
+
+    let response_text = match tokio::time::timeout(Duration::from_secs(timeout_secs), request).await {
+        Ok(result) => result?,
         Err(_) => {
-            debug_print!("❌ Request Timeout (120 seconds exceeded)");
+            debug_print!("❌ Request Timeout ({} seconds exceeded)", timeout_secs);
             debug_print!("This usually means:");
             debug_print!("  • Ollama is still loading the model (first run)");
             debug_print!("  • The model is too large for your system");
             debug_print!("  • Check Ollama logs for errors");
-            return Err(format!("❌ Ollama request timeout after 120 seconds on {}. Is the model too large or is Ollama still loading?", base_url));
+            return Err(ApiError::Unreachable(format!("❌ Ollama request timeout after {} seconds on {}. Is the model too large or is Ollama still loading?", timeout_secs, base_url)));
         }
     };
 
-    let response_text = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read Ollama response: {}", e))?;
-
-    debug_print!("🔍 [OLLAMA DEBUG - FIRST RESPONSE]");
+    debug_print!("🔍 [OLLAMA DEBUG - RESPONSE]");
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!("Raw Response Text:");
     debug_print!("{}", response_text);
@@ -148,76 +769,691 @@ If not a task, use "no command returned" for command."#,
     let response_data: serde_json::Value = serde_json::from_str(&response_text)
         .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
 
-    debug_print!("🔍 [OLLAMA DEBUG - PARSED JSON]");
+    let content = ollama_content(&response_data)?;
+
+    debug_print!("🔍 [OLLAMA DEBUG - EXTRACTED CONTENT]");
     debug_print!("────────────────────────────────────────────────────────────");
-    debug_print!("{}", serde_json::to_string_pretty(&response_data).unwrap_or_default());
+    debug_print!("{}", content);
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!();
 
+    Ok(content.to_string())
+}
+
+// Ollama reports problems like unknown models as {"error": "..."}, even with a 200 status
+fn ollama_content(response_data: &serde_json::Value) -> Result<&str, String> {
+    if let Some(error) = response_data.get("error").and_then(|e| e.as_str()) {
+        return Err(format!("Ollama error: {}", error));
+    }
+
     let content = response_data
         .get("response")
         .and_then(|c| c.as_str())
         .ok_or("Invalid response format from Ollama")?;
 
-    debug_print!("🔍 [OLLAMA DEBUG - EXTRACTED CONTENT]");
-    debug_print!("────────────────────────────────────────────────────────────");
+    Ok(strip_thinking(content))
+}
+
+/// Reads a streamed `/api/generate` reply: one `{"response": "...", "done": ...}`
+/// object per line. Returns it in the non-streamed shape, so the rest of
+/// ollama_generate doesn't care which it got.
+async fn read_ollama_stream(response: reqwest::Response) -> Result<String, String> {
+    set_stream_preview("");
+    let content = collect_stream_tokens(response).await;
+    set_stream_preview("");
+
+    let content = content?;
+    debug_print!("🔍 [OLLAMA DEBUG - STREAMED CONTENT]");
     debug_print!("{}", content);
-    debug_print!("────────────────────────────────────────────────────────────");
-    debug_print!();
 
-    let mut parsed: CommandSuggestion = {
-        let mut result = serde_json::from_str(content);
-
-        if result.is_err() {
-            debug_print!("⚠️  First parse attempt failed, trying to extract JSON...");
-            
-            // Try to remove markdown code block formatting (```json ... ```)
-            let mut clean_content = content.to_string();
-            if clean_content.starts_with("```") {
-                // Remove opening ```json or ```
-                if let Some(start_idx) = clean_content.find('\n') {
-                    clean_content = clean_content[start_idx + 1..].to_string();
-                }
+    Ok(serde_json::json!({ "response": content }).to_string())
+}
+
+// Tokens are collected until they hold a complete JSON object or the stream
+// ends. Returning early drops the response, which stops Ollama generating
+// whatever it would have added after the object.
+async fn collect_stream_tokens(mut response: reqwest::Response) -> Result<String, String> {
+    let mut decoder = Utf8Decoder::default();
+    let mut pending = String::new();
+    let mut content = String::new();
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read Ollama response: {}", e))?
+    {
+        // A character or a line can be split across chunks; only whole lines are parsed
+        pending.push_str(&decoder.push(&chunk));
+        while let Some(end) = pending.find('\n') {
+            let line: String = pending.drain(..=end).collect();
+            if add_stream_line(&line, &mut content)? {
+                return Ok(content);
             }
-            if clean_content.ends_with("```") {
-                clean_content.truncate(clean_content.len() - 3);
-            }
-            
-            // Now try to extract JSON
-            if let Some(start) = clean_content.find('{') {
-                if let Some(end) = clean_content.rfind('}') {
-                    if end > start {
-                        let extracted = &clean_content[start..=end];
-                        debug_print!("Extracted JSON (after markdown cleanup):");
-                        debug_print!("{}", extracted);
-                        result = serde_json::from_str(extracted);
-                    }
+        }
+    }
+
+    // The last line may come without a newline
+    pending.push_str(&decoder.finish());
+    add_stream_line(&pending, &mut content)?;
+    Ok(content)
+}
+
+// Appends one streamed line's token to `content`; true once the reply is done
+fn add_stream_line(line: &str, content: &mut String) -> Result<bool, String> {
+    if line.trim().is_empty() {
+        return Ok(false);
+    }
+
+    let data: serde_json::Value = serde_json::from_str(line.trim())
+        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+    if let Some(error) = data.get("error").and_then(|e| e.as_str()) {
+        return Err(format!("Ollama error: {}", error));
+    }
+    if let Some(token) = data.get("response").and_then(|t| t.as_str()) {
+        content.push_str(token);
+        set_stream_preview(content);
+    }
+    Ok(data.get("done").and_then(|d| d.as_bool()) == Some(true) || json_object_complete(content))
+}
+
+// Whether `text` holds a whole `{...}` object: braces balance once the first
+// one closes, ignoring any inside strings. Only what follows a leading <think>
+// block counts, since reasoning can quote braces of its own (`find -exec rm {} \;`).
+fn json_object_complete(text: &str) -> bool {
+    let mut text = text.trim_start();
+    if text.starts_with("<think>") {
+        match text.find("</think>") {
+            Some(end) => text = &text[end + "</think>".len()..],
+            None => return false,
+        }
+    }
+    let Some(start) = text.find('{') else {
+        return false;
+    };
+
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in text[start..].chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
                 }
             }
+            _ => {}
         }
+    }
+    false
+}
+
+/// Drops `<think>...</think>` reasoning that some models inline in their reply
+/// (Ollama returns it in a separate `thinking` field when `think` is honoured).
+fn strip_thinking(content: &str) -> &str {
+    match content.rfind("</think>") {
+        Some(end) if content.trim_start().starts_with("<think>") => &content[end + "</think>".len()..],
+        _ => content,
+    }
+}
+
+/// Loads the configured Ollama model into memory so the first real query isn't slow.
+/// A generate request without a prompt only loads the model.
+pub async fn warm_up_ollama(settings: &Settings) -> Result<(), String> {
+    ensure_online()?;
+
+    let base_url = settings
+        .ollama_base_url
+        .as_deref()
+        .unwrap_or("http://localhost:11434");
+
+    let model = settings
+        .ollama_model
+        .as_ref()
+        .ok_or("Ollama model not configured")?;
 
-        result.map_err(|e| format!("Failed to parse command suggestion: {}", e))?
+    let request_body = serde_json::json!({
+        "model": model,
+        "keep_alive": OLLAMA_KEEP_ALIVE
+    });
+
+    reqwest::Client::new()
+        .post(format!("{}/api/generate", base_url))
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| format!("Ollama warm-up failed: {}", e))?;
+
+    debug_print!("🔥 Ollama model {} warmed up", model);
+    Ok(())
+}
+
+/// An OpenAI-style `/chat/completions` endpoint (Cerebras, OpenAI, Groq, Mistral
+/// and LM Studio all speak it).
+struct ChatEndpoint<'a> {
+    provider: &'static str,
+    url: &'a str,
+    api_key: Option<&'a str>, // LM Studio takes none
+    model: &'a str,
+    timeout_secs: u64,
+}
+
+async fn cerebras_chat(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    let endpoint = ChatEndpoint {
+        provider: "Cerebras",
+        url: "https://api.cerebras.ai/v1/chat/completions",
+        api_key: Some(settings.cerebras_api_key.as_deref().ok_or("Cerebras API key not configured")?),
+        model: settings.cerebras_model.as_deref().ok_or("Cerebras model not configured")?,
+        timeout_secs: 120,
+    };
+    chat_completion(&endpoint, prompt, system, settings, max_tokens).await
+}
+
+async fn openai_chat(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    let endpoint = ChatEndpoint {
+        provider: "OpenAI",
+        url: "https://api.openai.com/v1/chat/completions",
+        api_key: Some(settings.openai_api_key.as_deref().ok_or("OpenAI API key not configured")?),
+        model: settings.openai_model.as_deref().ok_or("OpenAI model not configured")?,
+        timeout_secs: 120,
+    };
+    chat_completion(&endpoint, prompt, system, settings, max_tokens).await
+}
+
+async fn mistral_chat(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    let endpoint = ChatEndpoint {
+        provider: "Mistral",
+        url: "https://api.mistral.ai/v1/chat/completions",
+        api_key: Some(settings.mistral_api_key.as_deref().ok_or("Mistral API key not configured")?),
+        model: settings.mistral_model.as_deref().ok_or("Mistral model not configured")?,
+        timeout_secs: 120,
+    };
+    chat_completion(&endpoint, prompt, system, settings, max_tokens).await
+}
+
+// A local server like Ollama, so it gets Ollama's generous timeout
+async fn lmstudio_chat(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    let url = format!("{}/v1/chat/completions", lmstudio_base_url(settings));
+    let endpoint = ChatEndpoint {
+        provider: "LM Studio",
+        url: &url,
+        api_key: None,
+        model: settings.lmstudio_model.as_deref().ok_or("LM Studio model not configured")?,
+        timeout_secs: 120,
+    };
+    chat_completion(&endpoint, prompt, system, settings, max_tokens)
+        .await
+        .map_err(|e| match e {
+            ApiError::Unreachable(message) => {
+                ApiError::Unreachable(format!("{} Make sure the LM Studio server is running and a model is loaded.", message))
+            }
+            e => e,
+        })
+}
+
+fn lmstudio_base_url(settings: &Settings) -> &str {
+    settings
+        .lmstudio_base_url
+        .as_deref()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_LMSTUDIO_URL)
+        .trim_end_matches('/')
+}
+
+// Groq answers far faster than the others, so a stalled request is given up sooner
+async fn groq_chat(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    let endpoint = ChatEndpoint {
+        provider: "Groq",
+        url: "https://api.groq.com/openai/v1/chat/completions",
+        api_key: Some(settings.groq_api_key.as_deref().ok_or("Groq API key not configured")?),
+        model: settings.model().ok_or("Groq model not configured")?,
+        timeout_secs: 30,
     };
+    chat_completion(&endpoint, prompt, system, settings, max_tokens).await
+}
+
+// Ollama runner options. The other providers reject them as unknown fields, so
+// they only go into Ollama's "options".
+const OLLAMA_ONLY_PARAMS: &[&str] = &[
+    "num_ctx",
+    "num_predict",
+    "num_keep",
+    "num_batch",
+    "num_gpu",
+    "main_gpu",
+    "num_thread",
+    "repeat_last_n",
+    "repeat_penalty",
+    "mirostat",
+    "mirostat_eta",
+    "mirostat_tau",
+    "tfs_z",
+    "typical_p",
+    "penalize_newline",
+    "low_vram",
+    "use_mmap",
+    "use_mlock",
+    "numa",
+];
+
+/// extra_params for every provider but Ollama, without the Ollama-only options.
+fn hosted_extra_params(settings: &Settings) -> serde_json::Map<String, serde_json::Value> {
+    settings
+        .extra_params
+        .iter()
+        .filter(|(key, _)| !OLLAMA_ONLY_PARAMS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+async fn chat_completion(endpoint: &ChatEndpoint<'_>, prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    ensure_online()?;
+
+    let client = reqwest::Client::new();
+
+    let mut request_body = serde_json::json!({
+        "model": endpoint.model,
+        "messages": [
+            {
+                "role": "system",
+                "content": system
+            },
+            {
+                "role": "user",
+                "content": prompt
+            }
+        ],
+        "temperature": 0.3,
+        "max_tokens": max_tokens
+    });
+    // Sampling parameters go at the top level; extra_params win
+    if let Some(body) = request_body.as_object_mut() {
+        body.extend(hosted_extra_params(settings));
+    }
+
+    let request = client
+        .post(endpoint.url)
+        .timeout(Duration::from_secs(endpoint.timeout_secs))
+        .json(&request_body);
+    let request = match endpoint.api_key {
+        Some(api_key) => request.header("Authorization", format!("Bearer {}", api_key)),
+        None => request,
+    };
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| network_error(endpoint.provider, &e))?;
+
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+
+    if !status.is_success() {
+        return Err(ApiError::Failed(http_status_error(endpoint.provider, status, &response_text)));
+    }
+
+    let response_data: serde_json::Value = serde_json::from_str(&response_text)
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    // Cerebras reports rate limiting in the body as well
+    if let Some(error_type) = response_data.get("type").and_then(|t| t.as_str()) {
+        if error_type == "too_many_requests_error" {
+            return Err(ApiError::from(response_data.get("message").and_then(|m| m.as_str()).unwrap_or("API rate limit exceeded")));
+        }
+    }
+
+    Ok(chat_content(endpoint.provider, &response_data)?)
+}
+
+// Says whether the request never got an answer because of the network or a timeout
+fn network_error(provider: &str, error: &reqwest::Error) -> ApiError {
+    if error.is_timeout() {
+        ApiError::Unreachable(format!("{} request timed out. Check your connection and try again.", provider))
+    } else if error.is_connect() {
+        ApiError::Unreachable(format!("Could not connect to {} ({}). Check your network connection.", provider, error))
+    } else {
+        ApiError::Failed(format!("Request failed: {}", error))
+    }
+}
+
+/// Anthropic's Messages API: the system prompt goes in its own `system` field
+/// and the reply comes back as content blocks rather than choices.
+async fn anthropic_messages(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<String, ApiError> {
+    ensure_online()?;
+
+    let api_key = settings
+        .anthropic_api_key
+        .as_ref()
+        .ok_or("Anthropic API key not configured")?;
+
+    let model = settings
+        .anthropic_model
+        .as_ref()
+        .ok_or("Anthropic model not configured")?;
+
+    let mut request_body = serde_json::json!({
+        "model": model,
+        "system": system,
+        "messages": [
+            {
+                "role": "user",
+                "content": prompt
+            }
+        ],
+        "temperature": 0.3,
+        "max_tokens": max_tokens
+    });
+    if let Some(body) = request_body.as_object_mut() {
+        body.extend(hosted_extra_params(settings));
+    }
+
+    let response = reqwest::Client::new()
+        .post("https://api.anthropic.com/v1/messages")
+        .header("x-api-key", api_key)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .timeout(Duration::from_secs(120))
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| network_error("Anthropic", &e))?;
 
-    debug_print!("🔍 [OLLAMA DEBUG - PARSED COMMAND SUGGESTION]");
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+
+    if !status.is_success() {
+        return Err(ApiError::Failed(http_status_error("Anthropic", status, &response_text)));
+    }
+
+    let response_data: serde_json::Value = serde_json::from_str(&response_text)
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    if response_data.get("stop_reason").and_then(|r| r.as_str()) == Some("max_tokens") {
+        warn("⚠️  Anthropic response was truncated (max_tokens reached); the suggestion may be incomplete.");
+    }
+
+    response_data
+        .get("content")
+        .and_then(|c| c.get(0))
+        .and_then(|block| block.get("text"))
+        .and_then(|t| t.as_str())
+        .map(|t| t.to_string())
+        .ok_or_else(|| ApiError::from("Invalid response format from API"))
+}
+
+/// Gemini's reply text, and whether Google rated it HIGH for dangerous content.
+struct GeminiReply {
+    text: String,
+    dangerous_content: bool,
+}
+
+/// Gemini's `generateContent`: the key goes in the query string, the system
+/// prompt in `systemInstruction` and sampling parameters in `generationConfig`.
+async fn gemini_generate(prompt: &str, system: &str, settings: &Settings, max_tokens: u32) -> Result<GeminiReply, ApiError> {
+    ensure_online()?;
+
+    let api_key = settings
+        .gemini_api_key
+        .as_ref()
+        .ok_or("Gemini API key not configured")?;
+
+    let model = settings
+        .gemini_model
+        .as_ref()
+        .ok_or("Gemini model not configured")?;
+
+    let mut generation_config = serde_json::json!({
+        "temperature": 0.3,
+        "maxOutputTokens": max_tokens
+    });
+    let mut params = hosted_extra_params(settings);
+    if let Some(max_tokens) = params.remove("max_tokens") {
+        params.insert("maxOutputTokens".to_string(), max_tokens);
+    }
+    if let Some(config) = generation_config.as_object_mut() {
+        config.extend(params);
+    }
+
+    let request_body = serde_json::json!({
+        "systemInstruction": {
+            "parts": [{ "text": system }]
+        },
+        "contents": [
+            {
+                "role": "user",
+                "parts": [{ "text": prompt }]
+            }
+        ],
+        "generationConfig": generation_config
+    });
+
+    // The URL carries the key, so it is left out of every error message
+    let response = reqwest::Client::new()
+        .post(format!("https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent", model))
+        .query(&[("key", api_key)])
+        .timeout(Duration::from_secs(120))
+        .json(&request_body)
+        .send()
+        .await
+        .map_err(|e| network_error("Gemini", &e.without_url()))?;
+
+    let status = response.status();
+    let response_text = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read response: {}", e.without_url()))?;
+
+    if !status.is_success() {
+        return Err(ApiError::Failed(http_status_error("Gemini", status, &response_text)));
+    }
+
+    let response_data: serde_json::Value = serde_json::from_str(&response_text)
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+    Ok(gemini_content(&response_data)?)
+}
+
+fn gemini_content(response_data: &serde_json::Value) -> Result<GeminiReply, String> {
+    if let Some(reason) = response_data.pointer("/promptFeedback/blockReason").and_then(|r| r.as_str()) {
+        return Err(format!("Gemini blocked the prompt ({}). Try rephrasing the question.", reason));
+    }
+
+    let candidate = match response_data.get("candidates").and_then(|c| c.as_array()) {
+        Some(candidates) if !candidates.is_empty() => &candidates[0],
+        _ => return Err("Gemini returned no candidates (the response may have been filtered). Try rephrasing the question.".to_string()),
+    };
+
+    match candidate.get("finishReason").and_then(|f| f.as_str()) {
+        Some("SAFETY") => {
+            return Err("Gemini blocked the response with its safety filter. Try rephrasing the question.".to_string());
+        }
+        Some("MAX_TOKENS") => {
+            warn("⚠️  Gemini response was truncated (max_tokens reached); the suggestion may be incomplete.");
+        }
+        _ => {}
+    }
+
+    let dangerous_content = candidate
+        .get("safetyRatings")
+        .and_then(|r| r.as_array())
+        .is_some_and(|ratings| {
+            ratings.iter().any(|rating| {
+                rating.get("category").and_then(|c| c.as_str()) == Some("HARM_CATEGORY_DANGEROUS_CONTENT")
+                    && rating.get("probability").and_then(|p| p.as_str()) == Some("HIGH")
+            })
+        });
+
+    let text = candidate
+        .pointer("/content/parts/0/text")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| "Invalid response format from API".to_string())?;
+
+    Ok(GeminiReply {
+        text: text.to_string(),
+        dangerous_content,
+    })
+}
+
+/// Turns a non-2xx response into a readable error, keeping the provider's own
+/// message (`error`, `error.message` or `message`) when the body has one.
+fn http_status_error(provider: &str, status: reqwest::StatusCode, body: &str) -> String {
+    let detail = serde_json::from_str::<serde_json::Value>(body).ok().and_then(|json| {
+        json.get("error")
+            .and_then(|e| e.as_str().or_else(|| e.get("message").and_then(|m| m.as_str())))
+            .or_else(|| json.get("message").and_then(|m| m.as_str()))
+            .map(|m| m.to_string())
+    });
+
+    let summary = match status.as_u16() {
+        401 => format!("{} rejected the API key (401). Check the key or run 'tella --settings'.", provider),
+        403 => format!("{} refused the request (403 Forbidden). The key may lack access to this model.", provider),
+        404 => format!("{} endpoint or model not found (404). Check the configured model and URL.", provider),
+        429 => format!("{} rate limit exceeded (429). Wait a moment and try again.", provider),
+        code if status.is_server_error() => format!("{} server error ({}). Try again later.", provider, code),
+        code => format!("{} request failed with HTTP {}.", provider, code),
+    };
+
+    match detail {
+        Some(detail) => format!("{} ({} says: {})", summary, provider, detail),
+        None => summary,
+    }
+}
+
+fn chat_content(provider: &str, response_data: &serde_json::Value) -> Result<String, String> {
+    let choice = match response_data.get("choices").and_then(|c| c.as_array()) {
+        Some(choices) if choices.is_empty() => {
+            return Err(format!("{} returned no choices (the response may have been filtered). Try rephrasing the question.", provider));
+        }
+        Some(choices) => &choices[0],
+        None => return Err("Invalid response format from API".to_string()),
+    };
+
+    match choice.get("finish_reason").and_then(|f| f.as_str()) {
+        Some("content_filter") => {
+            return Err(format!("{} blocked the response with its content filter. Try rephrasing the question.", provider));
+        }
+        Some("length") => {
+            warn(&format!("⚠️  {} response was truncated (max_tokens reached); the suggestion may be incomplete.", provider));
+        }
+        _ => {}
+    }
+
+    choice
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_str())
+        .map(|c| c.to_string())
+        .ok_or_else(|| "Invalid response format from API".to_string())
+}
+
+// The first call of the two-pass flow (Ollama, Anthropic, Mistral): only the fields the
+// output settings show, with the explanation left to a second request. Shared with
+// --raw-response so it shows exactly what the suggestion sees.
+fn two_pass_command_prompt(question: &str, context: &PromptContext, settings: &Settings) -> String {
+    let shell_type = shell_type();
+
+    // Build the JSON response format based on output settings
+    let mut json_fields = vec![];
+    if settings.output_settings.show_command {
+        json_fields.push("\"command\": \"exact command\"");
+    }
+    if settings.output_settings.show_description {
+        json_fields.push("\"description\": \"brief desc\"");
+    }
+    if settings.output_settings.show_severity {
+        json_fields.push("\"severity\": \"safe|warning|dangerous\"");
+        json_fields.push("\"severity_description\": \"risk\"");
+    }
+    // Folded into this call so the rationale costs no extra request
+    if settings.inline_explanation {
+        json_fields.push(RATIONALE_FIELD);
+    }
+    if settings.help_exploration {
+        json_fields.push(KIND_FIELD);
+    }
+    if settings.check_prerequisites {
+        json_fields.push(PREREQUISITES_FIELD);
+    }
+    
+    let json_format = json_fields.join(",\n    ");
+
+    // First call: Get command and description only
+    let prompt = match prompt_template_for(settings) {
+        Some(template) => render_prompt_template(template, shell_type, question, &context.render(settings, question.len()), &json_format),
+        None => format!(
+            r#"Suggest the best {} command for: {}{}
+
+Respond with ONLY valid JSON (no markdown, no extra text):
+{{
+    {}
+}}
+
+If not a task, use "no command returned" for command."#,
+            shell_type, question, context.render(settings, question.len()), json_format
+        ),
+    };
+    with_kind_hint(prompt, settings)
+}
+
+async fn get_command_from_ollama(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = two_pass_command_prompt(question, context, settings);
+
+    debug_print!("Output Settings:");
+    debug_print!("  show_command: {}", settings.output_settings.show_command);
+    debug_print!("  show_description: {}", settings.output_settings.show_description);
+    debug_print!("  show_severity: {}", settings.output_settings.show_severity);
+    debug_print!("  show_explanation: {}", settings.output_settings.show_explanation);
+
+    let content = ollama_generate(&prompt, system_prompt(settings), settings, 120, true, true).await?;
+    Ok(add_explanation_pass(&content, context, settings).await?)
+}
+
+async fn get_command_from_anthropic(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = two_pass_command_prompt(question, context, settings);
+    let content = anthropic_messages(&prompt, system_prompt(settings), settings, 500).await?;
+    Ok(add_explanation_pass(&content, context, settings).await?)
+}
+
+async fn get_command_from_mistral(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = two_pass_command_prompt(question, context, settings);
+    let content = mistral_chat(&prompt, system_prompt(settings), settings, 500).await?;
+    Ok(add_explanation_pass(&content, context, settings).await?)
+}
+
+/// Parses the first reply of the two-pass flow and, when explanations are
+/// shown, asks for one in a second request.
+async fn add_explanation_pass(content: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, String> {
+    let shell_type = shell_type();
+
+    // With ollama_json_format the reply is plain JSON and parses on the first
+    // attempt; the extraction is only reached for models that ignore "format"
+    let mut parsed: CommandSuggestion = extract_json(content, '{', '}')
+        .map_err(|e| format!("Failed to parse command suggestion: {}", e))?;
+
+    debug_print!("🔍 [DEBUG - PARSED COMMAND SUGGESTION]");
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!("{}", serde_json::to_string_pretty(&parsed).unwrap_or_default());
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!();
 
-    // Fill in missing fields with defaults based on output settings
-    if !settings.output_settings.show_description && parsed.description.is_empty() {
-        parsed.description = String::new();
-    }
-    if !settings.output_settings.show_severity {
-        parsed.severity = String::new();
-        parsed.severity_description = String::new();
-    }
+    mark_missing_command(&mut parsed);
+    apply_kind_defaults(&mut parsed);
 
     // If command is "ERROR" or "no command returned", return early without explanation
     if parsed.command == "ERROR" || parsed.command == "no command returned" {
         debug_print!("ℹ️  No command returned, skipping explanation request");
-        parsed.explanation = "Unable to find a suitable command for this request.".to_string();
+        if parsed.explanation.is_empty() {
+            parsed.explanation = "Unable to find a suitable command for this request.".to_string();
+        }
         return Ok(parsed);
     }
 
@@ -235,74 +1471,35 @@ If not a task, use "no command returned" for command."#,
 Respond with ONLY valid JSON (no markdown, no extra text):
 {{
     "explanation": "detailed explanation of what this command does and why it's recommended"
-}}
+}}{}
 "#,
-        shell_type, parsed.command
+        shell_type, parsed.command, context.explanation_instruction(settings)
     );
 
-    let explanation_body = serde_json::json!({
-        "model": model,
-        "prompt": explanation_prompt,
-        "temperature": 0.3,
-        "stream": false,
-        "keep_alive": "5m"
-    });
+    debug_print!("{}", "🔍 [DEBUG - SECOND REQUEST (EXPLANATION)]".cyan().bold());
 
-    debug_print!("{}", "🔍 [OLLAMA DEBUG - SECOND REQUEST (EXPLANATION)]".cyan().bold());
-    debug_print!("{}", "─".repeat(60).cyan());
-    debug_print!("{}", "Request Body:".cyan().bold());
-    debug_print!("{}", serde_json::to_string_pretty(&explanation_body).unwrap_or_default());
-    debug_print!("{}", "─".repeat(60).cyan());
-    debug_print!();
+    let explanation = match settings.provider.as_str() {
+        "anthropic" => anthropic_messages(&explanation_prompt, system_prompt(settings), settings, 1000).await,
+        "mistral" => mistral_chat(&explanation_prompt, system_prompt(settings), settings, 1000).await,
+        _ => ollama_generate(&explanation_prompt, system_prompt(settings), settings, 60, true, false).await,
+    };
 
     // Don't fail if explanation fetch fails, just use a default
-    if let Ok(Ok(exp_response)) = tokio::time::timeout(
-        std::time::Duration::from_secs(60),
-        client.post(&url).json(&explanation_body).send(),
-    )
-    .await
-    {
-        if let Ok(exp_text) = exp_response.text().await {
-            debug_print!("{}", "🔍 [OLLAMA DEBUG - SECOND RESPONSE]".cyan().bold());
-            debug_print!("{}", "─".repeat(60).cyan());
-            debug_print!("{}", "Raw Response Text:".cyan().bold());
-            debug_print!("{}", exp_text);
-            debug_print!("{}", "─".repeat(60).cyan());
-            debug_print!();
-
-            if let Ok(exp_data) = serde_json::from_str::<serde_json::Value>(&exp_text) {
-                debug_print!("{}", "🔍 [OLLAMA DEBUG - EXPLANATION PARSED]".cyan().bold());
-                debug_print!("{}", "─".repeat(60).cyan());
-                debug_print!("{}", serde_json::to_string_pretty(&exp_data).unwrap_or_default());
-                debug_print!("{}", "─".repeat(60).cyan());
-                debug_print!();
-
-                if let Some(exp_content) = exp_data.get("response").and_then(|c| c.as_str()) {
-                    if let Ok(exp_json) = serde_json::from_str::<serde_json::Value>(exp_content) {
-                        if let Some(explanation) = exp_json.get("explanation").and_then(|e| e.as_str()) {
-                            debug_print!("{} {}", "✅ Explanation found:".green().bold(), explanation);
-                            parsed.explanation = explanation.to_string();
-                        }
-                    } else if let Some(start) = exp_content.find('{') {
-                        if let Some(end) = exp_content.rfind('}') {
-                            if end > start {
-                                if let Ok(exp_json) = serde_json::from_str::<serde_json::Value>(&exp_content[start..=end]) {
-                                    if let Some(explanation) = exp_json.get("explanation").and_then(|e| e.as_str()) {
-                                        debug_print!("{} {}", "✅ Explanation found (extracted):".green().bold(), explanation);
-                                        parsed.explanation = explanation.to_string();
-                                    }
-                                }
-                            }
-                        }
-                    }
+    match explanation {
+        Ok(exp_content) => {
+            if let Ok(exp_json) = extract_json::<serde_json::Value>(&exp_content, '{', '}') {
+                if let Some(explanation) = exp_json.get("explanation").and_then(|e| e.as_str()) {
+                    debug_print!("{} {}", "✅ Explanation found:".green().bold(), explanation);
+                    parsed.explanation = explanation.to_string();
                 }
             }
         }
-    } else {
-        debug_print!("{}", "⚠️  Explanation request timed out or failed".yellow().bold());
+        Err(e) => {
+            debug_print!("{} {}", "⚠️  Explanation request timed out or failed:".yellow().bold(), e);
+        }
     }
 
-    debug_print!("🔍 [OLLAMA DEBUG - FINAL RESULT]");
+    debug_print!("🔍 [DEBUG - FINAL RESULT]");
     debug_print!("────────────────────────────────────────────────────────────");
     debug_print!("{}", serde_json::to_string_pretty(&parsed).unwrap_or_default());
     debug_print!("────────────────────────────────────────────────────────────");
@@ -311,29 +1508,32 @@ Respond with ONLY valid JSON (no markdown, no extra text):
     Ok(parsed)
 }
 
-async fn get_command_from_cerebras(question: &str, settings: &Settings) -> Result<CommandSuggestion, String> {
-    let api_key = settings
-        .cerebras_api_key
-        .as_ref()
-        .ok_or("Cerebras API key not configured")?;
-
-    let model = settings
-        .ollama_model
-        .as_ref()
-        .ok_or("Cerebras model not configured")?;
-
-    let os = env::consts::OS;
-    let shell_type = match os {
-        "windows" => "PowerShell",
-        "linux" => "bash",
-        "macos" => "shell",
-        _ => "shell",
-    };
-
-    let client = reqwest::Client::new();
+fn chat_command_prompt(question: &str, context: &PromptContext, settings: &Settings) -> String {
+    let shell_type = shell_type();
+    let mut extra_fields = String::new();
+    if settings.inline_explanation {
+        extra_fields.push_str(&format!(",\n    {}", RATIONALE_FIELD));
+    }
+    if settings.help_exploration {
+        extra_fields.push_str(&format!(",\n    {}", KIND_FIELD));
+    }
+    if settings.check_prerequisites {
+        extra_fields.push_str(&format!(",\n    {}", PREREQUISITES_FIELD));
+    }
 
-    let prompt = format!(
-        r#"Suggest the best {} command for: {}
+    let prompt = match prompt_template_for(settings) {
+        Some(template) => render_prompt_template(
+            template,
+            shell_type,
+            question,
+            &context.render(settings, question.len()),
+            &format!(
+                "\"command\": \"exact command\",\n    \"description\": \"brief desc\",\n    \"explanation\": \"details\",\n    \"severity\": \"safe|warning|dangerous\",\n    \"severity_description\": \"risk\"{}",
+                extra_fields
+            ),
+        ),
+        None => format!(
+            r#"Suggest the best {} command for: {}{}
 
 If it's a task, respond with JSON:
 {{
@@ -341,80 +1541,675 @@ If it's a task, respond with JSON:
     "description": "brief desc",
     "explanation": "details",
     "severity": "safe|warning|dangerous",
-    "severity_description": "risk"
+    "severity_description": "risk"{}
 }}
 
-If not a task, use "no command returned"."#,
-        shell_type, question
-    );
+If not a task, use "no command returned".{}"#,
+            shell_type, question, context.render(settings, question.len()), extra_fields, context.explanation_instruction(settings)
+        ),
+    };
+    with_kind_hint(prompt, settings)
+}
 
-    let request_body = serde_json::json!({
-        "model": model,
-        "messages": [
-            {
-                "role": "system",
-                "content": "You are a command suggestion tool. Suggest commands or 'no command returned'. Always JSON."
-            },
-            {
-                "role": "user",
-                "content": prompt
-            }
-        ],
-        "temperature": 0.3,
-        "max_tokens": 500
-    });
+async fn get_command_from_cerebras(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = chat_command_prompt(question, context, settings);
+    let content = cerebras_chat(&prompt, system_prompt(settings), settings, 500).await?;
+    Ok(parse_chat_suggestion(&content)?)
+}
 
-    let response = client
-        .post("https://api.cerebras.ai/v1/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&request_body)
-        .send()
-        .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+async fn get_command_from_groq(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = chat_command_prompt(question, context, settings);
+    let content = groq_chat(&prompt, system_prompt(settings), settings, 500).await?;
+    Ok(parse_chat_suggestion(&content)?)
+}
 
-    let response_text = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
+// Google's own safety rating overrides a milder severity from the model
+async fn get_command_from_gemini(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = chat_command_prompt(question, context, settings);
+    let reply = gemini_generate(&prompt, system_prompt(settings), settings, 500).await?;
+    let mut parsed = parse_chat_suggestion(&reply.text)?;
 
-    // eprintln!("🔍 Debug: Full API response: {}", response_text);
+    if reply.dangerous_content && parsed.severity != "dangerous" {
+        parsed.severity = "dangerous".to_string();
+        parsed.severity_description = match parsed.severity_description.is_empty() {
+            true => "Gemini rated this response as dangerous content.".to_string(),
+            false => format!("Gemini rated this response as dangerous content. {}", parsed.severity_description),
+        };
+    }
 
-    let response_data: serde_json::Value = serde_json::from_str(&response_text)
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
+    Ok(parsed)
+}
 
-    // Check for API error responses
-    if let Some(error_type) = response_data.get("type").and_then(|t| t.as_str()) {
-        if error_type == "too_many_requests_error" {
-            return Err(response_data.get("message").and_then(|m| m.as_str()).unwrap_or("API rate limit exceeded").to_string());
-        }
-        // Add other error types if needed
+async fn get_command_from_lmstudio(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = chat_command_prompt(question, context, settings);
+    let content = lmstudio_chat(&prompt, system_prompt(settings), settings, 500).await?;
+    Ok(parse_chat_suggestion(&content)?)
+}
+
+async fn get_command_from_openai(question: &str, context: &PromptContext, settings: &Settings) -> Result<CommandSuggestion, ApiError> {
+    let prompt = chat_command_prompt(question, context, settings);
+    let content = openai_chat(&prompt, system_prompt(settings), settings, 500).await?;
+    Ok(parse_chat_suggestion(&content)?)
+}
+
+fn parse_chat_suggestion(content: &str) -> Result<CommandSuggestion, String> {
+    let mut parsed: CommandSuggestion = extract_json(content, '{', '}')
+        .map_err(|e| format!("Failed to parse command suggestion: {}", e))?;
+    mark_missing_command(&mut parsed);
+    apply_kind_defaults(&mut parsed);
+
+    Ok(parsed)
+}
+
+const SEVERITIES: [&str; 3] = ["safe", "warning", "dangerous"];
+
+/// Makes a parsed suggestion match what the output settings show. Fields that
+/// are switched off are cleared even when the model sent them, except the
+/// severity: `--yes` and the dangerous-command confirmation go by it, so a
+/// hidden severity is only left out when rendering. A shown
+/// description or severity the model left out (or a severity that isn't one of
+/// the three levels) is asked for once more when `online`, then defaulted. A
+/// missing explanation stays empty: Explain fetches one on demand.
+async fn reconcile_fields(suggestion: &mut CommandSuggestion, settings: &Settings, online: bool) {
+    let output = &settings.output_settings;
+    if !output.show_description {
+        suggestion.description.clear();
+    }
+    if !output.show_explanation {
+        suggestion.explanation.clear();
     }
 
-    let content = response_data
-        .get("choices")
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("message"))
-        .and_then(|m| m.get("content"))
-        .and_then(|c| c.as_str())
-        .ok_or("Invalid response format from API")?;
-
-    // eprintln!("🔍 Debug: API raw response: {}", content);
-
-    let parsed: CommandSuggestion = {
-        let mut result = serde_json::from_str(content);
-        
-        if result.is_err() {
-            if let Some(start) = content.find('{') {
-                if let Some(end) = content.rfind('}') {
-                    if end > start {
-                        result = serde_json::from_str(&content[start..=end]);
+    if suggestion.command == "ERROR" || suggestion.command == "no command returned" {
+        return;
+    }
+
+    // "Safe" or " warning" are close enough; "low" isn't
+    suggestion.severity = suggestion.severity.trim().to_lowercase();
+    let missing_description = output.show_description && suggestion.description.trim().is_empty();
+    let missing_severity = output.show_severity && !SEVERITIES.contains(&suggestion.severity.as_str());
+
+    if (missing_description || missing_severity) && online {
+        match request_missing_fields(&suggestion.command, missing_description, missing_severity, settings).await {
+            Ok(reply) => {
+                if missing_description {
+                    suggestion.description = reply.description;
+                }
+                if missing_severity {
+                    suggestion.severity = reply.severity.trim().to_lowercase();
+                    if !reply.severity_description.is_empty() {
+                        suggestion.severity_description = reply.severity_description;
                     }
                 }
             }
+            Err(e) => debug_print!("⚠️  Re-requesting missing fields failed: {}", e),
         }
-        
-        result.map_err(|e| format!("Failed to parse command suggestion: {}", e))?
-    };
+    }
+
+    if missing_description && suggestion.description.trim().is_empty() {
+        suggestion.description = "No description returned.".to_string();
+    }
+    if missing_severity && !SEVERITIES.contains(&suggestion.severity.as_str()) {
+        suggestion.severity = "unknown".to_string();
+        suggestion.severity_description = "The model didn't rate this command.".to_string();
+    }
+}
+
+// One follow-up request for just the fields the first reply left out
+async fn request_missing_fields(command: &str, description: bool, severity: bool, settings: &Settings) -> Result<CommandSuggestion, String> {
+    let mut fields = Vec::new();
+    if description {
+        fields.push("\"description\": \"brief desc\"");
+    }
+    if severity {
+        fields.push("\"severity\": \"safe|warning|dangerous\"");
+        fields.push("\"severity_description\": \"risk\"");
+    }
+
+    let prompt = format!(
+        r#"Describe this {} command: {}
+
+Respond with ONLY valid JSON (no markdown, no extra text):
+{{
+    {}
+}}"#,
+        shell_type(),
+        command,
+        fields.join(",\n    ")
+    );
+
+    let content = complete(&prompt, settings).await?;
+    extract_json(&content, '{', '}').map_err(|e| format!("Failed to parse missing fields: {}", e))
+}
+
+// A reply without "command" (or with a blank one) parses with an empty command;
+// treat it like "no command returned" so its description and explanation are
+// shown instead of an empty runnable line
+fn mark_missing_command(parsed: &mut CommandSuggestion) {
+    if !parsed.command.trim().is_empty() {
+        return;
+    }
+    parsed.command = "no command returned".to_string();
+    if parsed.description.is_empty() {
+        parsed.description = "The model didn't return a command.".to_string();
+    }
+}
+
+/// Asks the provider for `count` alternative commands for the same question.
+pub async fn get_command_suggestions(question: &str, context: &PromptContext, count: usize) -> Result<Vec<CommandSuggestion>, String> {
+    let settings = Settings::load()?;
+
+    let prompt = format!(
+        r#"Suggest {} different {} commands for: {}{}
+
+Each alternative should take a different approach. Respond with ONLY a valid JSON array (no markdown, no extra text):
+[
+    {{
+        "command": "exact command",
+        "description": "brief desc",
+        "explanation": "one or two sentences",
+        "severity": "safe|warning|dangerous",
+        "severity_description": "risk"
+    }}
+]"#,
+        count, shell_type(), question, context.render(&settings, question.len())
+    );
+
+    let content = complete(&prompt, &settings).await?;
+
+    let mut suggestions: Vec<CommandSuggestion> = extract_json(&content, '[', ']')
+        .map_err(|e| format!("Failed to parse alternatives: {}", e))?;
+
+    suggestions.retain(|s| !s.command.trim().is_empty() && s.command != "no command returned");
+    suggestions.truncate(count);
+
+    if suggestions.is_empty() {
+        return Err("No alternatives returned".to_string());
+    }
+
+    for suggestion in &mut suggestions {
+        reconcile_fields(suggestion, &settings, !is_offline()).await;
+        suggestion.command = postprocess_command(&suggestion.command, &settings);
+    }
+
+    Ok(suggestions)
+}
+
+/// Explains and classifies an existing command instead of generating one.
+pub async fn explain_command(command: &str, context: &PromptContext) -> Result<CommandSuggestion, String> {
+    let settings = Settings::load()?;
+
+    let prompt = format!(
+        r#"Explain this {} command: {}{}
+
+Respond with ONLY valid JSON (no markdown, no extra text):
+{{
+    "description": "brief desc",
+    "explanation": "detailed explanation of what this command does",
+    "severity": "safe|warning|dangerous",
+    "severity_description": "risk"
+}}{}"#,
+        shell_type(), command, context.render(&settings, command.len()), context.explanation_instruction(&settings)
+    );
+
+    let content = complete(&prompt, &settings).await?;
+
+    let mut parsed: CommandSuggestion = extract_json(&content, '{', '}')
+        .map_err(|e| format!("Failed to parse explanation: {}", e))?;
+
+    // Whatever the model echoes back, the command is the user's
+    parsed.command = command.to_string();
 
     Ok(parsed)
 }
+
+/// Explains `command` at the given level of detail, for the interactive Explain step.
+pub async fn explain_at_verbosity(command: &str, verbosity: ExplainVerbosity, context: &PromptContext) -> Result<String, String> {
+    let settings = Settings::load()?;
+
+    let prompt = format!(
+        r#"Explain this {} command: {}{}
+
+{} Answer in plain text (no JSON, no markdown code blocks).{}"#,
+        shell_type(),
+        command,
+        context.render(&settings, command.len()),
+        verbosity.instruction(),
+        context.explanation_instruction(&settings)
+    );
+
+    let content = complete_with_system(&prompt, PLAIN_TEXT_SYSTEM_PROMPT, &settings).await?;
+    Ok(content.trim().to_string())
+}
+
+/// Diagnoses a failed command. The reply's `explanation` holds the diagnosis and
+/// `command` a fix, or "no command returned" when there is nothing to run.
+pub async fn diagnose_error(command: &str, exit_code: Option<i32>, stderr: &str, context: &PromptContext) -> Result<CommandSuggestion, String> {
+    let settings = Settings::load()?;
+
+    let status = exit_code.map_or("did not exit normally".to_string(), |code| code.to_string());
+    let prompt = format!(
+        r#"This {} command failed: {}{}
+Exit status: {}
+Error output:
+```
+{}
+```
+
+Diagnose the failure and suggest ONE command that fixes it. Respond with ONLY valid JSON (no markdown, no extra text):
+{{
+    "explanation": "what went wrong and why",
+    "command": "fix command, or \"no command returned\" if the fix isn't a command",
+    "description": "brief desc of the fix",
+    "severity": "safe|warning|dangerous",
+    "severity_description": "risk"
+}}"#,
+        shell_type(), command, context.render(&settings, command.len() + stderr.len()), status, stderr.trim_end()
+    );
+
+    let content = complete(&prompt, &settings).await?;
+
+    extract_json(&content, '{', '}').map_err(|e| format!("Failed to parse diagnosis: {}", e))
+}
+
+/// Asks the model what a command's output means, e.g. why it failed.
+pub async fn explain_output(command: &str, exit_code: Option<i32>, output: &str, context: &PromptContext) -> Result<String, String> {
+    let settings = Settings::load()?;
+
+    let status = exit_code.map_or("terminated by signal".to_string(), |code| code.to_string());
+    let prompt = format!(
+        r#"I ran this {} command: {}{}
+Exit status: {}
+Output:
+```
+{}
+```
+
+Explain in plain text (no JSON, no markdown) what this output means. If the command failed, say why and how to fix it. Be brief."#,
+        shell_type(), command, context.render(&settings, command.len() + output.len()), status, output.trim_end()
+    );
+
+    let content = complete_with_system(&prompt, PLAIN_TEXT_SYSTEM_PROMPT, &settings).await?;
+    Ok(content.trim().to_string())
+}
+
+const HEALTH_CHECK_TIMEOUT_SECS: u64 = 5;
+
+/// Result of pinging one provider for `tella --provider-status`.
+pub struct ProviderHealth {
+    pub provider: &'static str,
+    pub endpoint: String,
+    pub model: Option<String>,
+    pub latency: Result<Duration, String>,
+}
+
+/// Pings every provider that has enough configuration to be used.
+pub async fn health_check_all(settings: &Settings) -> Vec<ProviderHealth> {
+    let active_model = |provider: &str| {
+        (settings.provider == provider).then(|| settings.model().map(str::to_string)).flatten()
+    };
+
+    let mut results = Vec::new();
+    if settings.provider == "ollama" || settings.ollama_base_url.is_some() {
+        let base_url = settings.ollama_base_url.as_deref().unwrap_or("http://localhost:11434");
+        results.push(ProviderHealth {
+            provider: "ollama",
+            endpoint: base_url.to_string(),
+            model: active_model("ollama"),
+            latency: ollama_health_check(base_url).await,
+        });
+    }
+    if settings.provider == "lmstudio" || settings.lmstudio_base_url.is_some() {
+        let base_url = lmstudio_base_url(settings);
+        results.push(ProviderHealth {
+            provider: "lmstudio",
+            endpoint: base_url.to_string(),
+            model: active_model("lmstudio"),
+            latency: timed_health_request(reqwest::Client::new().get(format!("{}/v1/models", base_url))).await,
+        });
+    }
+    if settings.provider == "cerebras" || settings.cerebras_api_key.is_some() {
+        results.push(ProviderHealth {
+            provider: "cerebras",
+            endpoint: "https://api.cerebras.ai".to_string(),
+            model: active_model("cerebras"),
+            latency: bearer_health_check("https://api.cerebras.ai/v1/models", settings.cerebras_api_key.as_deref()).await,
+        });
+    }
+    if settings.provider == "anthropic" || settings.anthropic_api_key.is_some() {
+        results.push(ProviderHealth {
+            provider: "anthropic",
+            endpoint: "https://api.anthropic.com".to_string(),
+            model: active_model("anthropic"),
+            latency: anthropic_health_check(settings.anthropic_api_key.as_deref()).await,
+        });
+    }
+    if settings.provider == "groq" || settings.groq_api_key.is_some() {
+        results.push(ProviderHealth {
+            provider: "groq",
+            endpoint: "https://api.groq.com".to_string(),
+            model: active_model("groq"),
+            latency: bearer_health_check("https://api.groq.com/openai/v1/models", settings.groq_api_key.as_deref()).await,
+        });
+    }
+    if settings.provider == "gemini" || settings.gemini_api_key.is_some() {
+        results.push(ProviderHealth {
+            provider: "gemini",
+            endpoint: "https://generativelanguage.googleapis.com".to_string(),
+            model: active_model("gemini"),
+            latency: gemini_health_check(settings.gemini_api_key.as_deref()).await,
+        });
+    }
+    if settings.provider == "mistral" || settings.mistral_api_key.is_some() {
+        results.push(ProviderHealth {
+            provider: "mistral",
+            endpoint: "https://api.mistral.ai".to_string(),
+            model: active_model("mistral"),
+            latency: bearer_health_check("https://api.mistral.ai/v1/models", settings.mistral_api_key.as_deref()).await,
+        });
+    }
+    if settings.provider == "openai" || settings.openai_api_key.is_some() {
+        results.push(ProviderHealth {
+            provider: "openai",
+            endpoint: "https://api.openai.com".to_string(),
+            model: active_model("openai"),
+            latency: bearer_health_check("https://api.openai.com/v1/models", settings.openai_api_key.as_deref()).await,
+        });
+    }
+    results
+}
+
+async fn ollama_health_check(base_url: &str) -> Result<Duration, String> {
+    let request = reqwest::Client::new().get(format!("{}/api/tags", base_url));
+    timed_health_request(request).await
+}
+
+// Listing models is free and still proves the key is accepted
+async fn bearer_health_check(url: &str, api_key: Option<&str>) -> Result<Duration, String> {
+    let api_key = api_key.ok_or("API key not configured")?;
+    let request = reqwest::Client::new()
+        .get(url)
+        .header("Authorization", format!("Bearer {}", api_key));
+    timed_health_request(request).await
+}
+
+async fn anthropic_health_check(api_key: Option<&str>) -> Result<Duration, String> {
+    let api_key = api_key.ok_or("API key not configured")?;
+    let request = reqwest::Client::new()
+        .get("https://api.anthropic.com/v1/models")
+        .header("x-api-key", api_key)
+        .header("anthropic-version", ANTHROPIC_VERSION);
+    timed_health_request(request).await
+}
+
+async fn gemini_health_check(api_key: Option<&str>) -> Result<Duration, String> {
+    let api_key = api_key.ok_or("API key not configured")?;
+    let request = reqwest::Client::new()
+        .get("https://generativelanguage.googleapis.com/v1beta/models")
+        .query(&[("key", api_key)]);
+    timed_health_request(request).await
+}
+
+async fn timed_health_request(request: reqwest::RequestBuilder) -> Result<Duration, String> {
+    ensure_online()?;
+    let start = Instant::now();
+    let response = tokio::time::timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS), request.send())
+        .await
+        .map_err(|_| format!("timed out after {}s", HEALTH_CHECK_TIMEOUT_SECS))?
+        .map_err(|e| format!("connection failed: {}", e.without_url()))?;
+    let elapsed = start.elapsed();
+
+    match response.status() {
+        status if status.is_success() => Ok(elapsed),
+        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::FORBIDDEN => {
+            Err("reachable, but the API key was rejected".to_string())
+        }
+        status => Err(format!("HTTP {}", status)),
+    }
+}
+
+/// What `tella --model-info` shows about the configured Ollama model.
+pub struct OllamaModelInfo {
+    pub name: String,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization: Option<String>,
+    pub context_length: Option<u64>,
+    pub size_bytes: Option<u64>,
+    pub parameters: Option<String>,
+}
+
+/// Reads model details from Ollama's `/api/show`, plus the on-disk size from `/api/tags`.
+pub async fn fetch_ollama_model_info(settings: &Settings) -> Result<OllamaModelInfo, String> {
+    ensure_online()?;
+
+    if settings.provider != "ollama" {
+        return Err(format!("--model-info only works with the ollama provider (current provider: {})", settings.provider));
+    }
+
+    let base_url = settings
+        .ollama_base_url
+        .as_deref()
+        .unwrap_or("http://localhost:11434");
+
+    let model = settings
+        .ollama_model
+        .as_ref()
+        .ok_or("Ollama model not configured")?;
+
+    let client = reqwest::Client::new();
+    let show: serde_json::Value = client
+        .post(format!("{}/api/show", base_url))
+        .json(&serde_json::json!({ "model": model }))
+        .send()
+        .await
+        .map_err(|e| format!("❌ Ollama connection failed: {}. Make sure Ollama is running on {}", e, base_url))?
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse Ollama response: {}", e))?;
+
+    if let Some(error) = show.get("error").and_then(|e| e.as_str()) {
+        return Err(format!("Ollama error: {}", error));
+    }
+
+    let detail = |key: &str| {
+        show.get("details")
+            .and_then(|d| d.get(key))
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+    };
+
+    // The key is prefixed with the architecture, e.g. "llama.context_length"
+    let context_length = show
+        .get("model_info")
+        .and_then(|info| info.as_object())
+        .and_then(|info| {
+            info.iter()
+                .find(|(key, _)| key.ends_with(".context_length"))
+                .and_then(|(_, value)| value.as_u64())
+        });
+
+    // Size is only listed by /api/tags; not worth failing over
+    let size_bytes = match client.get(format!("{}/api/tags", base_url)).send().await {
+        Ok(response) => response.json::<serde_json::Value>().await.ok().and_then(|tags| {
+            tags.get("models")?
+                .as_array()?
+                .iter()
+                .find(|m| m.get("name").and_then(|n| n.as_str()) == Some(model.as_str()))?
+                .get("size")?
+                .as_u64()
+        }),
+        Err(_) => None,
+    };
+
+    Ok(OllamaModelInfo {
+        name: model.clone(),
+        family: detail("family"),
+        parameter_size: detail("parameter_size"),
+        quantization: detail("quantization_level"),
+        context_length,
+        size_bytes,
+        parameters: show
+            .get("parameters")
+            .and_then(|p| p.as_str())
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| p.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(command: &str, description: &str, severity: &str) -> CommandSuggestion {
+        CommandSuggestion {
+            command: command.to_string(),
+            description: description.to_string(),
+            severity: severity.to_string(),
+            severity_description: "risk".to_string(),
+            explanation: "how it works".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn json_object_complete_ignores_braces_in_reasoning() {
+        let thinking = "<think>Could use find . -exec rm {} \\; here</think>\n";
+        assert!(!json_object_complete(thinking));
+        assert!(!json_object_complete(&format!("{}{{\"command\": \"find . -exec rm {{}} \\\\;\"", thinking)));
+        assert!(json_object_complete(&format!("{}{{\"command\": \"ls\"}}", thinking)));
+        assert!(!json_object_complete("<think>{\"a\": 1}"));
+    }
+
+    #[test]
+    fn ollama_error_body_is_reported() {
+        let response = serde_json::json!({ "error": "model 'x' not found" });
+        assert_eq!(ollama_content(&response).unwrap_err(), "Ollama error: model 'x' not found");
+
+        let mut content = String::new();
+        let error = add_stream_line("{\"error\": \"model 'x' not found\"}\n", &mut content).unwrap_err();
+        assert_eq!(error, "Ollama error: model 'x' not found");
+    }
+
+    #[test]
+    fn ollama_content_strips_thinking() {
+        let response = serde_json::json!({ "response": "<think>hmm</think>{\"command\": \"ls\"}", "done": true });
+        assert_eq!(ollama_content(&response).unwrap(), "{\"command\": \"ls\"}");
+        assert_eq!(ollama_content(&serde_json::json!({ "done": true })).unwrap_err(), "Invalid response format from Ollama");
+    }
+
+    #[test]
+    fn hosted_extra_params_leave_out_ollama_options() {
+        let mut settings = Settings::default();
+        settings.extra_params.insert("num_ctx".to_string(), serde_json::json!(8192));
+        settings.extra_params.insert("num_predict".to_string(), serde_json::json!(256));
+        settings.extra_params.insert("top_p".to_string(), serde_json::json!(0.9));
+
+        let params = hosted_extra_params(&settings);
+        assert_eq!(params.len(), 1);
+        assert_eq!(params["top_p"], serde_json::json!(0.9));
+    }
+
+    #[test]
+    fn chat_content_reads_the_first_choice() {
+        let response = serde_json::json!({
+            "choices": [{ "message": { "content": "{\"command\": \"ls\"}" }, "finish_reason": "stop" }]
+        });
+        assert_eq!(chat_content("Cerebras", &response).unwrap(), "{\"command\": \"ls\"}");
+    }
+
+    #[test]
+    fn chat_content_reports_empty_choices() {
+        let error = chat_content("Cerebras", &serde_json::json!({ "choices": [] })).unwrap_err();
+        assert!(error.starts_with("Cerebras returned no choices"), "{}", error);
+    }
+
+    #[test]
+    fn chat_content_reports_the_content_filter() {
+        let response = serde_json::json!({
+            "choices": [{ "message": { "content": "" }, "finish_reason": "content_filter" }]
+        });
+        let error = chat_content("Cerebras", &response).unwrap_err();
+        assert!(error.contains("content filter"), "{}", error);
+    }
+
+    #[test]
+    fn chat_content_keeps_a_truncated_reply() {
+        let response = serde_json::json!({
+            "choices": [{ "message": { "content": "{\"command\": \"ls" }, "finish_reason": "length" }]
+        });
+        assert_eq!(chat_content("Cerebras", &response).unwrap(), "{\"command\": \"ls");
+    }
+
+    #[test]
+    fn chat_content_rejects_other_shapes() {
+        let error = chat_content("Cerebras", &serde_json::json!({ "message": "nope" })).unwrap_err();
+        assert_eq!(error, "Invalid response format from API");
+    }
+
+    #[test]
+    fn missing_command_is_no_command_returned() {
+        let parsed = parse_chat_suggestion(r#"{"description": "Use the Settings app", "severity": "safe"}"#).unwrap();
+        assert_eq!(parsed.command, "no command returned");
+        assert_eq!(parsed.description, "Use the Settings app");
+
+        let parsed = parse_chat_suggestion(r#"{"command": "  ", "severity": "safe"}"#).unwrap();
+        assert_eq!(parsed.command, "no command returned");
+        assert_eq!(parsed.description, "The model didn't return a command.");
+
+        let parsed = parse_chat_suggestion(r#"{"command": "ls -la", "severity": "safe"}"#).unwrap();
+        assert_eq!(parsed.command, "ls -la");
+    }
+
+    #[tokio::test]
+    async fn missing_command_skips_the_explanation_pass() {
+        let settings = Settings::default();
+        let parsed = add_explanation_pass(r#"{"description": "Not possible from a shell"}"#, &PromptContext::default(), &settings)
+            .await
+            .unwrap();
+        assert_eq!(parsed.command, "no command returned");
+        assert_eq!(parsed.description, "Not possible from a shell");
+        assert_eq!(parsed.explanation, "Unable to find a suitable command for this request.");
+    }
+
+    #[tokio::test]
+    async fn reconcile_clears_disabled_fields_but_keeps_severity() {
+        let mut settings = Settings::default();
+        settings.output_settings.show_description = false;
+        settings.output_settings.show_severity = false;
+        settings.output_settings.show_explanation = false;
+
+        let mut parsed = suggestion("rm -rf /tmp/x", "Deletes x", "Dangerous");
+        reconcile_fields(&mut parsed, &settings, false).await;
+
+        assert_eq!(parsed.description, "");
+        assert_eq!(parsed.explanation, "");
+        assert_eq!(parsed.severity, "dangerous");
+        assert_eq!(parsed.severity_description, "risk");
+    }
+
+    #[tokio::test]
+    async fn reconcile_defaults_missing_fields_offline() {
+        let settings = Settings::default();
+
+        let mut parsed = suggestion("ls -la", "  ", "low");
+        reconcile_fields(&mut parsed, &settings, false).await;
+
+        assert_eq!(parsed.description, "No description returned.");
+        assert_eq!(parsed.severity, "unknown");
+        assert_eq!(parsed.severity_description, "The model didn't rate this command.");
+        assert_eq!(parsed.explanation, "how it works");
+    }
+
+    #[tokio::test]
+    async fn reconcile_leaves_complete_suggestions_alone() {
+        let settings = Settings::default();
+
+        let mut parsed = suggestion("ls -la", "Lists files", " Safe");
+        reconcile_fields(&mut parsed, &settings, true).await;
+
+        assert_eq!(parsed.description, "Lists files");
+        assert_eq!(parsed.severity, "safe");
+        assert_eq!(parsed.severity_description, "risk");
+    }
+}
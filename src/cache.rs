@@ -0,0 +1,113 @@
+use crate::api::{CommandSuggestion, PromptContext};
+use crate::clock::{self, now_secs};
+use crate::command_executor;
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+const DEFAULT_TTL_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    question: String,
+    suggestion: CommandSuggestion,
+    created_at: u64,
+}
+
+/// Collapses whitespace, trims and lowercases a question so that
+/// "list   files" and "List files" produce the same cache key.
+pub fn normalize_question(question: &str) -> String {
+    question
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+fn cache_file() -> Result<PathBuf, String> {
+    Ok(Settings::get_settings_path()?.join("cache.json"))
+}
+
+// FNV-1a, so keys stay stable across Rust versions. The shell, --cwd and the
+// explanation format are part of the key since each changes the answer.
+fn cache_key(question: &str, context: &PromptContext, settings: &Settings) -> String {
+    let material = format!(
+        "{}\u{0}{}\u{0}{}\u{0}{}\u{0}{:?}\u{0}{}",
+        settings.provider,
+        settings.model().unwrap_or(""),
+        command_executor::shell_program(),
+        context.cwd.as_ref().map(|cwd| cwd.display().to_string()).unwrap_or_default(),
+        context.effective_explain_format(settings),
+        normalize_question(question)
+    );
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in material.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+fn read_entries() -> HashMap<String, CacheEntry> {
+    cache_file()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn get(question: &str, context: &PromptContext, settings: &Settings) -> Option<CommandSuggestion> {
+    // With a clock that's clearly off, TTLs mean nothing; treat everything as a miss
+    if !clock::is_plausible() {
+        return None;
+    }
+
+    let ttl = settings.cache_ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    let entries = read_entries();
+    let entry = entries.get(&cache_key(question, context, settings))?;
+
+    if clock::age_secs(entry.created_at) > ttl {
+        return None;
+    }
+
+    Some(entry.suggestion.clone())
+}
+
+pub fn put(question: &str, context: &PromptContext, settings: &Settings, suggestion: &CommandSuggestion) -> Result<(), String> {
+    if !clock::is_plausible() {
+        return Ok(());
+    }
+
+    let ttl = settings.cache_ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    let now = now_secs();
+
+    let mut entries = read_entries();
+    // Entries stamped in the future (the clock moved back) are fresh, but
+    // restamped to now so they still expire one TTL from here
+    for entry in entries.values_mut() {
+        entry.created_at = entry.created_at.min(now);
+    }
+    entries.retain(|_, entry| now - entry.created_at <= ttl);
+    entries.insert(
+        cache_key(question, context, settings),
+        CacheEntry {
+            question: normalize_question(question),
+            suggestion: suggestion.clone(),
+            created_at: now,
+        },
+    );
+
+    fs::create_dir_all(Settings::get_settings_path()?)
+        .map_err(|e| format!("Failed to create cache directory: {}", e))?;
+
+    let content = serde_json::to_string(&entries)
+        .map_err(|e| format!("Failed to serialize cache: {}", e))?;
+
+    fs::write(cache_file()?, content)
+        .map_err(|e| format!("Failed to write cache file: {}", e))?;
+
+    Ok(())
+}
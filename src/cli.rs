@@ -1,130 +1,2288 @@
-use crate::api::get_command_suggestion;
-use crate::ui::MenuSelector;
+use crate::api::{self, diagnose_error, explain_at_verbosity, explain_command, explain_output, fetch_ollama_model_info, get_command_suggestion, get_command_suggestion_from, get_command_suggestions, get_raw_response, health_check_all, shell_type, stream_preview, Attachment, CommandSuggestion, ExplainFormat, ExplainVerbosity, PromptContext};
+use crate::ui::{self, ListPicker, MenuSelector};
+use crate::clock;
 use crate::command_executor;
-use crate::settings::Settings;
+use crate::history::{self, HistoryEntry};
+use crate::last_run;
+use crate::quiz;
+use crate::safety;
+use crate::settings::{OutputSettings, Settings};
 use colored::*;
-use std::io;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
-pub async fn handle_ask_command(question: &str) -> io::Result<()> {
-    let dot_handle = print_animated_dots();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Jsonl,
+    Markdown,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AskOptions {
+    pub quiet: bool,
+    pub json: bool,
+    pub no_trailing_newline: bool,
+    pub cwd: Option<PathBuf>,
+    pub log_output: Option<PathBuf>,
+    pub yes: bool,
+    pub first: bool,
+    pub alternatives: Option<usize>,
+    pub format: Option<OutputFormat>,
+    pub no_severity_gate: bool,
+    pub explain_format: Option<ExplainFormat>,
+    pub attachment: Option<Attachment>,
+    pub tags: Vec<String>,
+    pub explain_errors: bool,
+    pub pager: bool,
+    pub explain_verbosity: Option<ExplainVerbosity>,
+    pub interval: Option<u64>,
+    pub exec_pipe: Option<String>,
+}
+
+impl AskOptions {
+    fn prompt_context(&self) -> PromptContext {
+        PromptContext {
+            cwd: self.cwd.clone(),
+            explain_format: self.explain_format,
+            attachment: self.attachment.clone(),
+        }
+    }
+}
+
+pub async fn handle_ask_command(question: &str, options: &AskOptions) -> io::Result<()> {
+    if options.quiet || options.json || options.format.is_some() {
+        return handle_non_interactive(question, options).await;
+    }
+
+    // Load settings to get output preferences
+    let settings = Settings::load().ok();
+    let output_settings = settings.as_ref().map(|s| &s.output_settings);
+
+    if settings.as_ref().is_some_and(|s| s.detect_direct_commands) && looks_like_command(question) {
+        println!("{}", format!("\"{}\" looks like a command already.", question).cyan());
+        let menu = MenuSelector::new()
+            .add_option("Explain it", "")
+            .add_option("Run as-is", "")
+            .add_option("Ask tella", "");
+        match menu.show()? {
+            0 => return handle_explain_command(question, options).await,
+            1 => {
+                let suggestion = CommandSuggestion {
+                    command: question.to_string(),
+                    ..Default::default()
+                };
+                let blocklist = settings.as_ref().map(compile_blocklist).unwrap_or_default();
+                return run_suggestion(&suggestion, options, settings.as_ref(), &blocklist).await;
+            }
+            2 => {}
+            _ => {
+                println!("{}", "Goodbye!".yellow());
+                return Ok(());
+            }
+        }
+    }
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let result = cancellable(fetch_suggestions(question, options)).await;
+
+    // let elapsed = start.elapsed();
+    // eprintln!("🔍 Debug: API call took {:?}", elapsed);
+
+    spinner.stop();
+    api::release_warnings();
+
+    let Some(result) = result? else {
+        println!("{}", "Cancelled.".yellow());
+        return Ok(());
+    };
+
+    let suggestions = match result {
+        Ok(suggestions) => suggestions,
+        Err(e) => {
+            eprintln!("{}", format!("\n❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    let suggestion = if suggestions.len() == 1 || options.first {
+        suggestions[0].clone()
+    } else if options.yes {
+        let message = "--yes needs --first to choose between several alternatives";
+        eprintln!("{}", format!("❌ Error: {}", message).red());
+        return Err(io::Error::other(message));
+    } else {
+        match choose_suggestion(question, &suggestions, options, settings.as_ref()).await? {
+            AlternativeChoice::One(chosen) => chosen,
+            AlternativeChoice::RanAll => return Ok(()),
+            AlternativeChoice::Back => {
+                println!("{}", "Goodbye!".yellow());
+                return Ok(());
+            }
+        }
+    };
+
+    if suggestion.command == "ERROR" || suggestion.command == "no command returned" {
+        eprintln!("{}", suggestion.description.red());
+        eprintln!("{}", suggestion.explanation.yellow());
+        return Ok(());
+    }
+
+    display_suggestion(&suggestion, output_settings);
+    record_history(question, &suggestion, options, settings.as_ref());
+
+    present_suggestion(question, suggestion, settings.as_ref(), options).await
+}
+
+/// Several questions in one invocation (`--ask` or `--`-separated): each gets its
+/// own header, suggestion and menu. A failed question doesn't stop the rest;
+/// the last error is returned at the end.
+pub async fn handle_ask_commands(questions: &[String], options: &AskOptions) -> io::Result<()> {
+    let interactive = !(options.quiet || options.json || options.format.is_some());
+    let mut last_error = None;
+
+    for (i, question) in questions.iter().enumerate() {
+        if interactive {
+            if i > 0 {
+                println!();
+            }
+            println!("{}", format!("━━ Question {}/{}: {}", i + 1, questions.len(), question).bold().cyan());
+        }
+        if let Err(e) = handle_ask_command(question, options).await {
+            last_error = Some(e);
+        }
+    }
+
+    last_error.map_or(Ok(()), Err)
+}
+
+/// `tella --session`: a prompt loop. Every question's suggestion gets a number;
+/// `run N`, `explain N` and `list` act on earlier ones without asking again.
+pub async fn handle_session(options: &AskOptions) -> io::Result<()> {
+    let settings = Settings::load().ok();
+    let output_settings = settings.as_ref().map(|s| &s.output_settings);
+    let blocklist = settings.as_ref().map(compile_blocklist).unwrap_or_default();
+    let mut suggestions: Vec<(String, CommandSuggestion)> = Vec::new();
+
+    println!("{}", "Ask a question, or: run N, explain N, list, quit".dimmed());
+    loop {
+        print!("{} ", "tella>".bold().cyan());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            println!();
+            break;
+        }
+        let input = input.trim();
+
+        match parse_session_command(input) {
+            Some(SessionCommand::Quit) => break,
+            Some(SessionCommand::List) => {
+                if suggestions.is_empty() {
+                    println!("{}", "No suggestions yet.".dimmed());
+                }
+                for (i, (question, suggestion)) in suggestions.iter().enumerate() {
+                    println!("{} {}  {}", format!("[{}]", i + 1).cyan(), suggestion.command.bold(), format!("— {}", question).dimmed());
+                }
+            }
+            Some(SessionCommand::Run(n) | SessionCommand::Explain(n)) if n == 0 || n > suggestions.len() => {
+                println!("{}", format!("No suggestion {} (have {}).", n, suggestions.len()).yellow());
+            }
+            Some(SessionCommand::Run(n)) => {
+                let suggestion = &suggestions[n - 1].1;
+                // Catastrophic commands are confirmed in prepare_command, so don't ask twice
+                let catastrophic = safety::catastrophic_reason(&suggestion.command, settings.as_ref()).is_some();
+                if catastrophic || confirm_menu_run(suggestion, settings.as_ref())? {
+                    run_suggestion(suggestion, options, settings.as_ref(), &blocklist).await?;
+                } else {
+                    println!("{}", "Cancelled.".yellow());
+                }
+            }
+            Some(SessionCommand::Explain(n)) => {
+                explain_interactively(&suggestions[n - 1].1, options, settings.as_ref()).await?;
+            }
+            None if input.is_empty() => {}
+            None => {
+                let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+                let result = get_command_suggestion(input, &options.prompt_context()).await;
+                spinner.stop();
+
+                match result {
+                    Ok(suggestion) if suggestion.command == "ERROR" || suggestion.command == "no command returned" => {
+                        eprintln!("{}", suggestion.description.red());
+                    }
+                    Ok(suggestion) => {
+                        print!("{} ", format!("[{}]", suggestions.len() + 1).cyan());
+                        display_suggestion(&suggestion, output_settings);
+                        record_history(input, &suggestion, options, settings.as_ref());
+                        suggestions.push((input.to_string(), suggestion));
+                    }
+                    Err(e) => eprintln!("{}", format!("❌ Error: {}", e).red()),
+                }
+            }
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+enum SessionCommand {
+    Run(usize),
+    Explain(usize),
+    List,
+    Quit,
+}
+
+// Only exact forms count, so "run tests in 2 dirs" is still a question
+fn parse_session_command(input: &str) -> Option<SessionCommand> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    match words.as_slice() {
+        ["run", n] => n.parse().ok().map(SessionCommand::Run),
+        ["explain", n] => n.parse().ok().map(SessionCommand::Explain),
+        ["list"] => Some(SessionCommand::List),
+        ["quit" | "exit"] => Some(SessionCommand::Quit),
+        _ => None,
+    }
+}
+
+/// `tella --explain <command>`: explain an existing command instead of generating one.
+pub async fn handle_explain_command(command: &str, options: &AskOptions) -> io::Result<()> {
+    let settings = Settings::load().ok();
+    let output_settings = settings.as_ref().map(|s| &s.output_settings);
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let result = explain_command(command, &options.prompt_context()).await;
+    spinner.stop();
+
+    let suggestion = match result {
+        Ok(suggestion) => suggestion,
+        Err(e) => {
+            eprintln!("{}", format!("\n❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    display_suggestion(&suggestion, output_settings);
+    println!();
+    print_paged(&render_explanation(&suggestion.explanation), options, settings.as_ref());
+
+    present_suggestion(command, suggestion, settings.as_ref(), options).await
+}
+
+/// `tella --severity-only "<command>"`: prints just the model's classification
+/// and risk, one line (or `{"severity", "severity_description"}` with --json).
+pub async fn handle_severity_only(command: &str, options: &AskOptions) -> io::Result<()> {
+    let settings = Settings::load().ok();
+    let output_settings = settings.as_ref().map(|s| &s.output_settings);
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let result = explain_command(command, &options.prompt_context()).await;
+    spinner.stop();
+
+    let suggestion = match result {
+        Ok(suggestion) => suggestion,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    if options.json {
+        let json = serde_json::json!({
+            "severity": suggestion.severity,
+            "severity_description": suggestion.severity_description,
+        });
+        let json = serde_json::to_string_pretty(&json).map_err(io::Error::other)?;
+        return write_output(&json, !options.no_trailing_newline);
+    }
+
+    if suggestion.severity_description.is_empty() {
+        println!("{}", severity_label(&suggestion.severity, output_settings));
+    } else {
+        println!("{} - {}", severity_label(&suggestion.severity, output_settings), suggestion.severity_description);
+    }
+    Ok(())
+}
+
+/// `tella --diff "<my command>" question`: asks for a suggestion and prints a
+/// word-level diff from the user's command to it. Non-interactive.
+pub async fn handle_diff(my_command: &str, question: &str, options: &AskOptions) -> io::Result<()> {
+    let settings = Settings::load().ok();
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let result = get_command_suggestion(question, &options.prompt_context()).await;
+    spinner.stop();
+
+    let suggestion = match result {
+        Ok(suggestion) if suggestion.command != "ERROR" && suggestion.command != "no command returned" => suggestion,
+        Ok(suggestion) => {
+            eprintln!("{}", suggestion.description.red());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    println!("{} {}", "Yours:    ".dimmed(), my_command);
+    println!("{} {}", "Suggested:".dimmed(), suggestion.command.bold());
+    if my_command.trim() == suggestion.command.trim() {
+        println!("{}", "✅ Same as tella's suggestion.".green());
+    } else {
+        println!("{} {}", "Diff:     ".dimmed(), word_diff(my_command.trim(), suggestion.command.trim()));
+    }
+    if !suggestion.description.is_empty() {
+        println!("\n{}", suggestion.description.dimmed());
+    }
+    Ok(())
+}
+
+// Removed words struck through in red, added ones underlined in green; without
+// a terminal, git's plain word-diff markers [-removed-]{+added+}
+fn word_diff(old: &str, new: &str) -> String {
+    let color = io::stdout().is_terminal();
+    similar::TextDiff::from_words(old, new)
+        .iter_all_changes()
+        .map(|change| match (change.tag(), color) {
+            (similar::ChangeTag::Equal, _) => change.value().to_string(),
+            (similar::ChangeTag::Delete, true) => change.value().red().strikethrough().to_string(),
+            (similar::ChangeTag::Insert, true) => change.value().green().underline().to_string(),
+            (similar::ChangeTag::Delete, false) => format!("[-{}-]", change.value()),
+            (similar::ChangeTag::Insert, false) => format!("{{+{}+}}", change.value()),
+        })
+        .collect()
+}
+
+/// `tella --raw-response question`: prints the model's unparsed reply and exits.
+pub async fn handle_raw_response(question: &str, options: &AskOptions) -> io::Result<()> {
+    let settings = Settings::load().ok();
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let result = get_raw_response(question, &options.prompt_context()).await;
+    spinner.stop();
+
+    match result {
+        Ok(content) => write_output(&content, !options.no_trailing_newline),
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            Err(io::Error::other(e))
+        }
+    }
+}
+
+const PICK_LIMIT: usize = 50;
+
+/// `tella --pick`: choose a recent command from history and run or copy it, without asking the model.
+pub async fn handle_pick(options: &AskOptions) -> io::Result<()> {
+    let entries = match history::load() {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    // Most recent first, each command once
+    let mut seen = std::collections::HashSet::new();
+    let recent: Vec<HistoryEntry> = entries
+        .into_iter()
+        .rev()
+        .filter(|entry| entry.has_tags(&options.tags))
+        .filter(|entry| seen.insert(entry.command.clone()))
+        .take(PICK_LIMIT)
+        .collect();
+
+    if recent.is_empty() {
+        println!("{}", "No history entries found.".yellow());
+        return Ok(());
+    }
+
+    let labels = recent
+        .iter()
+        .map(|entry| format!("{}  — {}", entry.command.replace('\n', " ⏎ "), entry.question))
+        .collect();
+    let entry = match ListPicker::new(labels).show()? {
+        Some(index) => &recent[index],
+        None => {
+            println!("{}", "Goodbye!".yellow());
+            return Ok(());
+        }
+    };
+
+    let settings = Settings::load().ok();
+    let suggestion = CommandSuggestion {
+        command: entry.command.clone(),
+        description: entry.description.clone(),
+        severity: entry.severity.clone(),
+        ..Default::default()
+    };
+    println!();
+    display_suggestion(&suggestion, settings.as_ref().map(|s| &s.output_settings));
+    println!();
+
+    let menu = MenuSelector::new()
+        .add_option("Run", "")
+        .add_option("Copy", "")
+        .add_option("Cancel", "");
+    match menu.show()? {
+        0 => {
+            let catastrophic = safety::catastrophic_reason(&suggestion.command, settings.as_ref()).is_some();
+            if !catastrophic && !confirm_menu_run(&suggestion, settings.as_ref())? {
+                println!("{}", "Cancelled.".yellow());
+                return Ok(());
+            }
+            let blocklist = settings.as_ref().map(compile_blocklist).unwrap_or_default();
+            run_suggestion(&suggestion, options, settings.as_ref(), &blocklist).await
+        }
+        1 => {
+            match command_executor::copy_to_clipboard(&suggestion.command) {
+                Ok(()) => println!("{}", "📋 Copied to clipboard".green()),
+                Err(e) => eprintln!("{}", format!("⚠️  {}", e).yellow()),
+            }
+            Ok(())
+        }
+        _ => {
+            println!("{}", "Goodbye!".yellow());
+            Ok(())
+        }
+    }
+}
+
+/// `tella --compare <A> <B> question`: asks two models at once, shows their
+/// answers side by side and runs the one the user picks.
+pub async fn handle_compare(question: &str, models: &[String], options: &AskOptions) -> io::Result<()> {
+    let [model_a, model_b] = models else {
+        let message = "--compare needs exactly two models";
+        eprintln!("{}", format!("❌ Error: {}", message).red());
+        return Err(io::Error::other(message));
+    };
+
+    let settings = Settings::load().ok();
+    let context = options.prompt_context();
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let (result_a, result_b) = tokio::join!(
+        get_command_suggestion_from(question, &context, model_a),
+        get_command_suggestion_from(question, &context, model_b)
+    );
+    spinner.stop();
+
+    print_side_by_side(&[(model_a, &result_a), (model_b, &result_b)], settings.as_ref().map(|s| &s.output_settings));
+
+    let candidates: Vec<(&String, &CommandSuggestion)> = [(model_a, &result_a), (model_b, &result_b)]
+        .into_iter()
+        .filter_map(|(model, result)| result.as_ref().ok().map(|suggestion| (model, suggestion)))
+        .filter(|(_, suggestion)| suggestion.command != "ERROR" && suggestion.command != "no command returned")
+        .collect();
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    println!();
+    let labels: Vec<String> = candidates.iter().map(|(model, _)| format!("Run {}", model)).collect();
+    let menu = labels
+        .iter()
+        .fold(MenuSelector::new(), |menu, label| menu.add_option(label, ""))
+        .add_option("Stop", "");
+    let (model, suggestion) = match candidates.get(menu.show()?) {
+        Some(&candidate) => candidate,
+        None => {
+            println!("{}", "Goodbye!".yellow());
+            return Ok(());
+        }
+    };
+
+    record_history(question, suggestion, options, settings.as_ref());
+    if !confirm_menu_run(suggestion, settings.as_ref())? {
+        println!("{}", "Cancelled.".yellow());
+        return Ok(());
+    }
+    println!("{}", format!("Running {}'s suggestion", model).dimmed());
+    let blocklist = settings.as_ref().map(compile_blocklist).unwrap_or_default();
+    run_suggestion(suggestion, options, settings.as_ref(), &blocklist).await
+}
+
+/// `tella --consensus question`: asks every model in consensus_models at once.
+/// When their commands agree it goes on as usual; otherwise each variant is shown
+/// with the models that suggested it, and you pick one (or none).
+pub async fn handle_consensus(question: &str, options: &AskOptions) -> io::Result<()> {
+    let settings = Settings::load().ok();
+    let models = settings.as_ref().map(|s| s.consensus_models.clone()).unwrap_or_default();
+    if models.len() < 2 {
+        let message = r#"--consensus needs at least two models in "consensus_models" in settings, e.g. ["ollama/llama3.2", "cerebras/llama3.3-70b"]"#;
+        eprintln!("{}", format!("❌ Error: {}", message).red());
+        return Err(io::Error::other(message));
+    }
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let mut requests = tokio::task::JoinSet::new();
+    for (i, model) in models.iter().enumerate() {
+        let (question, context, model) = (question.to_string(), options.prompt_context(), model.clone());
+        requests.spawn(async move { (i, get_command_suggestion_from(&question, &context, &model).await) });
+    }
+    let mut results: Vec<(usize, Result<CommandSuggestion, String>)> = Vec::new();
+    while let Some(result) = requests.join_next().await {
+        results.push(result.map_err(io::Error::other)?);
+    }
+    results.sort_by_key(|(i, _)| *i);
+    spinner.stop();
+
+    // Variants by normalized command, each with the models behind it
+    let mut variants: Vec<(CommandSuggestion, Vec<&str>)> = Vec::new();
+    for (i, result) in results {
+        let model = models[i].as_str();
+        let suggestion = match result {
+            Ok(suggestion) if suggestion.command != "ERROR" && suggestion.command != "no command returned" => suggestion,
+            Ok(suggestion) => {
+                eprintln!("{}", format!("⚠️  {}: {}", model, suggestion.description).yellow());
+                continue;
+            }
+            Err(e) => {
+                eprintln!("{}", format!("⚠️  {}: {}", model, e).yellow());
+                continue;
+            }
+        };
+        match variants
+            .iter_mut()
+            .find(|(variant, _)| normalize_command(&variant.command) == normalize_command(&suggestion.command))
+        {
+            // Agreeing models may still rate the risk differently; keep the most cautious
+            Some((variant, sources)) => {
+                if severity_rank(&suggestion.severity) > severity_rank(&variant.severity) {
+                    *variant = suggestion;
+                }
+                sources.push(model);
+            }
+            None => variants.push((suggestion, vec![model])),
+        }
+    }
+
+    let output_settings = settings.as_ref().map(|s| &s.output_settings);
+    let suggestion = match variants.as_slice() {
+        [] => {
+            let message = "No model returned a command";
+            eprintln!("{}", format!("❌ Error: {}", message).red());
+            return Err(io::Error::other(message));
+        }
+        [(suggestion, sources)] if sources.len() > 1 => {
+            println!("{}", format!("✅ {} models agree ({})", sources.len(), sources.join(", ")).green());
+            suggestion.clone()
+        }
+        _ => {
+            if variants.len() == 1 {
+                println!("{}", format!("⚠️  Only {} answered; nothing to cross-check", variants[0].1[0]).yellow());
+            } else {
+                println!("{}", format!("⚠️  The models disagree ({} variants)", variants.len()).yellow());
+            }
+            for (i, (suggestion, sources)) in variants.iter().enumerate() {
+                println!();
+                println!("{} {}", format!("{}.", i + 1).cyan(), suggestion.command.bold());
+                println!("   {} - {}", severity_label(&suggestion.severity, output_settings), suggestion.severity_description);
+                println!("   {}", format!("from {}", sources.join(", ")).dimmed());
+            }
+            println!();
+
+            let labels: Vec<String> = (1..=variants.len()).map(|i| format!("Use {}", i)).collect();
+            let menu = labels
+                .iter()
+                .fold(MenuSelector::new(), |menu, label| menu.add_option(label, ""))
+                .add_option("Stop", "");
+            match variants.get(menu.show()?) {
+                Some((suggestion, _)) => suggestion.clone(),
+                None => {
+                    println!("{}", "Goodbye!".yellow());
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    display_suggestion(&suggestion, output_settings);
+    record_history(question, &suggestion, options, settings.as_ref());
+
+    present_suggestion(question, suggestion, settings.as_ref(), options).await
+}
+
+// Differences that don't change what runs: spacing and a trailing `;`
+fn normalize_command(command: &str) -> String {
+    command
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Two columns sized to the terminal; text is wrapped, never truncated
+fn print_side_by_side(columns: &[(&String, &Result<CommandSuggestion, String>); 2], output_settings: Option<&OutputSettings>) {
+    let width = crossterm::terminal::size().map_or(100, |(cols, _)| cols as usize);
+    let column_width = (width.saturating_sub(3) / 2).max(20);
+
+    // (text, style) rows per column, styled after padding so colors don't skew widths
+    let rows: Vec<Vec<(String, &str)>> = columns
+        .iter()
+        .map(|(model, result)| {
+            let mut rows = vec![(model.to_string(), "header")];
+            match result {
+                Ok(suggestion) => {
+                    rows.extend(wrap(&suggestion.command, column_width).into_iter().map(|l| (l, "command")));
+                    rows.push((severity_text(&suggestion.severity).to_string(), &suggestion.severity));
+                    rows.extend(wrap(&suggestion.description, column_width).into_iter().map(|l| (l, "dimmed")));
+                }
+                Err(e) => rows.extend(wrap(&format!("❌ {}", e), column_width).into_iter().map(|l| (l, "error"))),
+            }
+            rows
+        })
+        .collect();
+
+    let style = |text: &str, kind: &str| -> String {
+        match kind {
+            "header" => text.bold().cyan().underline().to_string(),
+            "command" => text.bold().yellow().to_string(),
+            "safe" | "warning" | "dangerous" => {
+                let styled = match severity_style(kind, output_settings).1 {
+                    Some(color) => text.color(color),
+                    None => text.normal(),
+                };
+                match kind {
+                    "dangerous" => styled.bold().to_string(),
+                    _ => styled.to_string(),
+                }
+            }
+            "error" => text.red().to_string(),
+            _ => text.dimmed().to_string(),
+        }
+    };
+
+    println!();
+    let height = rows.iter().map(|column| column.len()).max().unwrap_or(0);
+    for i in 0..height {
+        let (left, left_kind) = rows[0].get(i).map_or(("", ""), |(text, kind)| (text.as_str(), *kind));
+        let (right, right_kind) = rows[1].get(i).map_or(("", ""), |(text, kind)| (text.as_str(), *kind));
+        let padding = " ".repeat(column_width.saturating_sub(left.chars().count()));
+        println!("{}{} {} {}", style(left, left_kind), padding, "│".dimmed(), style(right, right_kind));
+    }
+}
+
+fn severity_text(severity: &str) -> &'static str {
+    match severity {
+        "safe" => "SAFE",
+        "warning" => "WARNING",
+        "dangerous" => "DANGEROUS",
+        _ => "UNKNOWN",
+    }
+}
+
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for source_line in text.lines() {
+        let chars: Vec<char> = source_line.chars().collect();
+        if chars.is_empty() {
+            lines.push(String::new());
+        }
+        for chunk in chars.chunks(width.max(1)) {
+            lines.push(chunk.iter().collect());
+        }
+    }
+    lines
+}
+
+/// `tella --explain-last-output`: asks the model about the last command tella ran.
+pub async fn handle_explain_last_output(options: &AskOptions) -> io::Result<()> {
+    let last = match last_run::load() {
+        Ok(Some(last)) => last,
+        Ok(None) => {
+            println!("{}", "No command has been run through tella yet.".yellow());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    let settings = Settings::load().ok();
+    println!("{} {}", "$".cyan(), last.command.bold());
+    println!("{}", format!("(ran {})", clock::format_utc(last.timestamp)).dimmed());
+
+    let spinner = print_animated_dots(thinking_message(settings.as_ref()));
+    let result = explain_output(&last.command, last.exit_code, &last.output, &PromptContext::default()).await;
+    spinner.stop();
+
+    match result {
+        Ok(explanation) => {
+            println!();
+            print_paged(&explanation, options, settings.as_ref());
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}", format!("\n❌ Error: {}", e).red());
+            Err(io::Error::other(e))
+        }
+    }
+}
+
+/// `tella --provider-status`: pings each configured provider and prints one row per provider.
+pub async fn print_provider_status() -> io::Result<()> {
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    let results = health_check_all(&settings).await;
+
+    println!("{}", "Provider status".bold().cyan());
+    println!("{}", "━".repeat(50));
+    for health in &results {
+        let active = if health.provider == settings.provider { "*" } else { " " };
+        let status = match &health.latency {
+            Ok(latency) => format!("✅ reachable ({} ms)", latency.as_millis()).green(),
+            Err(e) => format!("❌ {}", e).red(),
+        };
+        println!("{} {:<9} {}", active, health.provider.bold(), status);
+        println!("    {}", format!("endpoint: {}", health.endpoint).dimmed());
+        if let Some(model) = &health.model {
+            println!("    {}", format!("model:    {}", model).dimmed());
+        }
+    }
+    println!("\n{}", "* active provider".dimmed());
+
+    Ok(())
+}
+
+/// `tella --validate-config`: every problem in settings.json with the line it's
+/// on. Fails (non-zero exit) when a real run would.
+pub fn handle_validate_config() -> io::Result<()> {
+    let (path, problems) = match Settings::validate_file() {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    println!("{}", format!("Checking {}", path.display()).bold());
+    if problems.is_empty() {
+        println!("{}", "✅ No problems found".green());
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!();
+        if problem.error {
+            println!("{}", format!("❌ {}", problem.message).red());
+        } else {
+            println!("{}", format!("⚠️  {}", problem.message).yellow());
+        }
+        if let Some((line, text)) = &problem.context {
+            println!("   {} {}", format!("{:>4} │", line).dimmed(), text);
+        }
+    }
+
+    let errors = problems.iter().filter(|problem| problem.error).count();
+    let summary = format!("{} error(s), {} warning(s)", errors, problems.len() - errors);
+    println!();
+    if errors > 0 {
+        println!("{}", summary.red().bold());
+        return Err(io::Error::other(format!("settings.json has {}", summary)));
+    }
+    println!("{}", summary.yellow());
+    Ok(())
+}
+
+/// `tella --model-info`: details about the configured Ollama model.
+pub async fn print_model_info() -> io::Result<()> {
+    let info = match Settings::load() {
+        Ok(settings) => fetch_ollama_model_info(&settings).await,
+        Err(e) => Err(e),
+    };
+    let info = match info {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    let unknown = || "unknown".to_string();
+    println!("{}", format!("Model: {}", info.name).bold().cyan());
+    println!("{}", "━".repeat(50));
+    println!("  {:<15} {}", "Family:", info.family.unwrap_or_else(unknown));
+    println!("  {:<15} {}", "Parameters:", info.parameter_size.unwrap_or_else(unknown));
+    println!("  {:<15} {}", "Quantization:", info.quantization.unwrap_or_else(unknown));
+    println!(
+        "  {:<15} {}",
+        "Context length:",
+        info.context_length.map_or_else(unknown, |n| format!("{} tokens", n))
+    );
+    println!("  {:<15} {}", "Size:", info.size_bytes.map_or_else(unknown, format_size));
+    if let Some(parameters) = info.parameters {
+        println!("\n{}", "Runtime parameters:".bold());
+        for line in parameters.lines() {
+            println!("  {}", line.trim().dimmed());
+        }
+    }
+
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MB: f64 = 1024.0 * 1024.0;
+    let bytes = bytes as f64;
+    if bytes >= GB {
+        format!("{:.1} GB", bytes / GB)
+    } else {
+        format!("{:.0} MB", bytes / MB)
+    }
+}
+
+// Input that already starts with an executable on PATH is probably a command, not a question
+fn looks_like_command(question: &str) -> bool {
+    let mut tokens = question.split_whitespace();
+    let first = match tokens.next() {
+        Some("sudo") => tokens.next(),
+        first => first,
+    };
+    first.is_some_and(|program| command_executor::find_in_path(program).is_some())
+}
+
+// The menu loop shared by generated and explained suggestions
+async fn present_suggestion(
+    question: &str,
+    mut suggestion: CommandSuggestion,
+    settings: Option<&Settings>,
+    options: &AskOptions,
+) -> io::Result<()> {
+    let output_settings = settings.map(|s| &s.output_settings);
+    let blocklist = settings.map(compile_blocklist).unwrap_or_default();
+
+    // Catastrophic commands are confirmed in prepare_command, so don't ask twice
+    let mut catastrophic = safety::catastrophic_reason(&suggestion.command, settings).is_some();
+
+    // Shown before any confirmation so it's clear where the command will run
+    if let Some(wrapper) = exec_wrapper(settings) {
+        println!("{}", format!("📦 Runs inside: {}", wrapper).dimmed());
+    }
+
+    if settings.is_some_and(|s| s.check_prerequisites) {
+        print_prerequisites(&suggestion.prerequisites);
+    }
+
+    let resolve_binary = settings.is_some_and(|s| s.resolve_binary_path);
+    let mut missing_program = if resolve_binary { preview_binary(&suggestion.command) } else { None };
+
+    if options.yes {
+        let gated = !options.no_severity_gate && !within_auto_run_threshold(&suggestion, settings);
+        if gated && !catastrophic && !confirm_run(&suggestion, settings)? {
+            println!("{}", "Cancelled.".yellow());
+            return Ok(());
+        }
+        return run_suggestion(&suggestion, options, settings, &blocklist).await;
+    }
+
+    // Rows taken by the explanation toggled open with 'x', so it can be erased again
+    let mut expanded_rows: Option<usize> = None;
+
+    println!();
+    loop {
+        let mut actions = vec!["Run"];
+
+        // Watch redraws the screen and waits for a keypress, so it needs a terminal
+        if io::stdin().is_terminal() && io::stdout().is_terminal() {
+            actions.push("Watch");
+        }
+
+        // Detecting the terminal emulator is unreliable, so it's opt-in. The new
+        // window's shell runs on the host, so it's not offered with exec_wrapper.
+        if terminal_window(settings).is_some() && exec_wrapper(settings).is_none() {
+            actions.push("Open in new window");
+        }
+
+        // Only add Explain option if explanation is enabled
+        let explain_enabled = output_settings.is_none_or(|o| o.show_explanation);
+        if explain_enabled {
+            actions.push("Explain");
+        }
+
+        if settings.is_some_and(|s| s.learn_mode) && quiz::is_quizzable(&suggestion.command) {
+            actions.push("Quiz me");
+        }
+
+        if missing_program.is_some() {
+            actions.push("How to install");
+        }
+
+        actions.push("Edit");
+        actions.push("More options");
+        actions.push("Stop");
+
+        let mut menu = actions
+            .iter()
+            .fold(MenuSelector::new(), |menu, action| menu.add_option(action, ""));
+        if explain_enabled && !suggestion.explanation.is_empty() {
+            let title = if expanded_rows.is_some() { "hide explanation" } else { "show explanation" };
+            menu = menu.add_hotkey('x', title);
+        }
+        let selected = menu.show()?;
+
+        if menu.hotkey(selected) == Some('x') {
+            expanded_rows = toggle_inline_explanation(&suggestion.explanation, expanded_rows)?;
+            continue;
+        }
+        // Anything else prints below, so the open explanation stays put
+        expanded_rows = None;
+
+        match actions.get(selected).copied() {
+            Some("Run") => {
+                if !catastrophic && !confirm_menu_run(&suggestion, settings)? {
+                    println!("{}", "Cancelled.".yellow());
+                    continue;
+                }
+                run_suggestion(&suggestion, options, settings, &blocklist).await?;
+                break;
+            }
+            Some("Watch") => {
+                if !catastrophic && !confirm_menu_run(&suggestion, settings)? {
+                    println!("{}", "Cancelled.".yellow());
+                    continue;
+                }
+                watch_suggestion(&suggestion, options, settings, &blocklist).await?;
+                break;
+            }
+            Some("Open in new window") => {
+                if !catastrophic && !confirm_menu_run(&suggestion, settings)? {
+                    println!("{}", "Cancelled.".yellow());
+                    continue;
+                }
+                let Some(command) = prepare_command(&suggestion, options, settings, &blocklist)? else {
+                    continue;
+                };
+                let terminal = terminal_window(settings).unwrap_or("auto");
+                match command_executor::open_in_new_terminal(&command, terminal, options.cwd.as_deref()) {
+                    Ok(name) => {
+                        println!("{}", format!("✅ Opened in {}", name).green());
+                        break;
+                    }
+                    Err(e) => eprintln!("{}", format!("❌ Error: {}", e).red()),
+                }
+            }
+            Some("Explain") => {
+                explain_interactively(&suggestion, options, settings).await?;
+                println!();
+            }
+            Some("Quiz me") => {
+                quiz::quiz_command(&suggestion.command, question)?;
+                println!();
+            }
+            Some("How to install") => {
+                if let Some(program) = &missing_program {
+                    offer_install(program, options, settings, &blocklist).await?;
+                    missing_program = preview_binary(&suggestion.command);
+                    println!();
+                }
+            }
+            Some("Edit") => {
+                let editor = ui::LineEditor::new(&suggestion.command, command_executor::programs_with_prefix);
+                match editor.show()? {
+                    Some(edited) if !edited.trim().is_empty() && edited != suggestion.command => {
+                        // The model's severity and explanation were for the old command
+                        suggestion = CommandSuggestion {
+                            command: edited,
+                            description: "Edited by you".to_string(),
+                            ..Default::default()
+                        };
+                        catastrophic = safety::catastrophic_reason(&suggestion.command, settings).is_some();
+                        println!();
+                        display_suggestion(&suggestion, output_settings);
+                        if resolve_binary {
+                            missing_program = preview_binary(&suggestion.command);
+                        }
+                        record_history(question, &suggestion, options, settings);
+                    }
+                    _ => {}
+                }
+                println!();
+            }
+            Some("More options") => {
+                match pick_alternative(question, options, settings).await? {
+                    AlternativeChoice::One(alternative) => {
+                        suggestion = alternative;
+                        catastrophic = safety::catastrophic_reason(&suggestion.command, settings).is_some();
+                        println!();
+                        display_suggestion(&suggestion, output_settings);
+                        if resolve_binary {
+                            missing_program = preview_binary(&suggestion.command);
+                        }
+                        record_history(question, &suggestion, options, settings);
+                        println!();
+                    }
+                    AlternativeChoice::RanAll => break,
+                    AlternativeChoice::Back => {}
+                }
+            }
+            _ => {
+                println!("{}", "Goodbye!".yellow());
+                break;
+            }
+        }
+    }
+
+    if let Some(reminder) = quiz::due_reminder(settings) {
+        println!("{}", reminder.dimmed());
+    }
+    Ok(())
+}
+
+// "docker running" is checked as docker on PATH plus an active docker unit;
+// anything else by its first word on PATH. Unverifiable ones are just listed.
+fn print_prerequisites(prerequisites: &[String]) {
+    if prerequisites.is_empty() {
+        return;
+    }
+
+    println!("{}", "Requires:".bold());
+    for prerequisite in prerequisites {
+        let mut words = prerequisite.split_whitespace();
+        let Some(program) = words.next() else {
+            continue;
+        };
+        let wants_running = words.any(|word| matches!(word, "running" | "service" | "daemon"));
+
+        let status = if command_executor::find_in_path(program).is_none() {
+            format!("❌ {} is not installed", program).red()
+        } else if !wants_running {
+            "✅ installed".green()
+        } else {
+            match command_executor::service_running(program) {
+                Some(true) => "✅ running".green(),
+                Some(false) => format!("❌ {} is not running", program).red(),
+                None => "⚪ installed; can't tell if it's running".normal(),
+            }
+        };
+        println!("  {} {}", prerequisite, status);
+    }
+}
+
+// Expands or collapses the explanation right above the menu. On a terminal the
+// block is erased again on collapse; otherwise expanding just prints it.
+// Returns the rows now taken by the expanded explanation.
+fn toggle_inline_explanation(explanation: &str, expanded_rows: Option<usize>) -> io::Result<Option<usize>> {
+    let inline = io::stdin().is_terminal() && io::stdout().is_terminal();
+    if !inline {
+        if expanded_rows.is_none() {
+            println!("\n{}\n", render_explanation(explanation));
+            return Ok(Some(0));
+        }
+        return Ok(None);
+    }
+
+    let mut stdout = io::stdout();
+    match expanded_rows {
+        // The menu line plus the explanation and its trailing blank line
+        Some(rows) => {
+            crossterm::execute!(
+                stdout,
+                crossterm::cursor::MoveToPreviousLine(rows as u16 + 1),
+                crossterm::terminal::Clear(crossterm::terminal::ClearType::FromCursorDown)
+            )?;
+            Ok(None)
+        }
+        None => {
+            crossterm::execute!(
+                stdout,
+                crossterm::cursor::MoveToPreviousLine(1),
+                crossterm::terminal::Clear(crossterm::terminal::ClearType::FromCursorDown)
+            )?;
+            let rendered = render_explanation(explanation);
+            let width = crossterm::terminal::size().map_or(80, |(cols, _)| cols as usize).max(1);
+            let rows: usize = rendered.lines().map(|line| line.chars().count().div_ceil(width).max(1)).sum();
+            println!("{}\n", rendered);
+            Ok(Some(rows + 1))
+        }
+    }
+}
+
+// The Explain step: shows the explanation, then lets the user switch between
+// short/medium/long. Each level is fetched once; the suggestion's own
+// explanation counts as medium.
+async fn explain_interactively(suggestion: &CommandSuggestion, options: &AskOptions, settings: Option<&Settings>) -> io::Result<()> {
+    let mut explanations: HashMap<ExplainVerbosity, String> = HashMap::new();
+    if !suggestion.explanation.is_empty() {
+        explanations.insert(ExplainVerbosity::Medium, suggestion.explanation.clone());
+    }
+
+    let mut verbosity = options.explain_verbosity.unwrap_or_default();
+    loop {
+        let explanation = match explanations.get(&verbosity) {
+            Some(explanation) => explanation,
+            None => {
+                let spinner = print_animated_dots(thinking_message(settings));
+                let result = explain_at_verbosity(&suggestion.command, verbosity, &options.prompt_context()).await;
+                spinner.stop();
+                match result {
+                    Ok(explanation) => explanations.entry(verbosity).or_insert(explanation),
+                    Err(e) => {
+                        eprintln!("{}", format!("❌ Error: {}", e).red());
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        println!("\n{}", format!("Explanation ({}):", verbosity.label()).bold());
+        print_paged(&render_explanation(explanation), options, settings);
+        println!();
+
+        let others: Vec<ExplainVerbosity> = ExplainVerbosity::ALL.into_iter().filter(|v| *v != verbosity).collect();
+        let labels: Vec<String> = others.iter().map(|v| format!("Make it {}", v.label())).collect();
+        let menu = labels
+            .iter()
+            .fold(MenuSelector::new(), |menu, label| menu.add_option(label, ""))
+            .add_option("Back", "");
+        match others.get(menu.show()?) {
+            Some(next) => verbosity = *next,
+            None => return Ok(()),
+        }
+    }
+}
+
+fn terminal_window(settings: Option<&Settings>) -> Option<&str> {
+    settings
+        .and_then(|s| s.terminal_window.as_deref())
+        .filter(|t| !t.trim().is_empty())
+}
+
+fn exec_wrapper(settings: Option<&Settings>) -> Option<&str> {
+    settings
+        .and_then(|s| s.exec_wrapper.as_deref())
+        .filter(|wrapper| !wrapper.trim().is_empty())
+}
+
+// With resolve_binary_path: shows which binary will actually run. Returns the
+// program when it isn't on PATH at all.
+fn preview_binary(command: &str) -> Option<String> {
+    match safety::resolve_binary(command) {
+        safety::BinaryLookup::Found(program, path) => {
+            println!("{}", format!("→ {} is {}", program, path.display()).dimmed());
+            None
+        }
+        safety::BinaryLookup::Missing(program) => {
+            eprintln!("{}", format!("⚠️  '{}' was not found on PATH.", program).yellow());
+            Some(program)
+        }
+        safety::BinaryLookup::Unknown => None,
+    }
+}
+
+// Asks the model how to install a missing program and offers to run that
+async fn offer_install(program: &str, options: &AskOptions, settings: Option<&Settings>, blocklist: &[Regex]) -> io::Result<()> {
+    let question = format!("install {}", program);
+    let spinner = print_animated_dots(thinking_message(settings));
+    let result = get_command_suggestion(&question, &options.prompt_context()).await;
+    spinner.stop();
+
+    let install = match result {
+        Ok(install) if install.command != "ERROR" && install.command != "no command returned" => install,
+        Ok(_) => {
+            eprintln!("{}", format!("No install command found for '{}'.", program).yellow());
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Ok(());
+        }
+    };
+
+    println!();
+    display_suggestion(&install, settings.map(|s| &s.output_settings));
+    println!();
+    let menu = MenuSelector::new().add_option("Run install", "").add_option("Back", "");
+    if menu.show()? != 0 {
+        return Ok(());
+    }
+    if !confirm_menu_run(&install, settings)? {
+        println!("{}", "Cancelled.".yellow());
+        return Ok(());
+    }
+    record_history(&question, &install, options, settings);
+    run_suggestion(&install, options, settings, blocklist).await
+}
+
+async fn fetch_suggestions(question: &str, options: &AskOptions) -> Result<Vec<CommandSuggestion>, String> {
+    match options.alternatives {
+        Some(count) if count > 1 => get_command_suggestions(question, &options.prompt_context(), count).await,
+        _ => get_command_suggestion(question, &options.prompt_context())
+            .await
+            .map(|suggestion| vec![suggestion]),
+    }
+}
+
+/// Runs the suggestion. When it fails, offers a diagnosis and a fix, and runs
+/// the fix the same way if the user picks it.
+async fn run_suggestion(
+    suggestion: &CommandSuggestion,
+    options: &AskOptions,
+    settings: Option<&Settings>,
+    blocklist: &[Regex],
+) -> io::Result<()> {
+    let mut current = suggestion.clone();
+    loop {
+        let failure = match run_once(&current, options, settings, blocklist).await? {
+            Some(failure) => failure,
+            None => return Ok(()),
+        };
+        match offer_diagnosis(&failure, options, settings).await? {
+            Some(fix) => current = fix,
+            None => return Ok(()),
+        }
+    }
+}
+
+// Seconds between runs in Watch when --interval isn't given, as with watch(1)
+const DEFAULT_WATCH_INTERVAL_SECS: u64 = 2;
+
+/// The "Watch" action: re-runs the command every `--interval` seconds, clearing
+/// the screen between runs, until q, Esc or Ctrl-C.
+async fn watch_suggestion(
+    suggestion: &CommandSuggestion,
+    options: &AskOptions,
+    settings: Option<&Settings>,
+    blocklist: &[Regex],
+) -> io::Result<()> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use crossterm::{cursor, execute, terminal};
+
+    let Some(command) = prepare_command(suggestion, options, settings, blocklist)? else {
+        return Ok(());
+    };
+
+    let interval = Duration::from_secs(options.interval.unwrap_or(DEFAULT_WATCH_INTERVAL_SECS).max(1));
+    let mut stdout = io::stdout();
+    loop {
+        let result = command_executor::execute_command(&command, options.cwd.as_deref(), exec_wrapper(settings)).await;
+
+        execute!(stdout, terminal::Clear(terminal::ClearType::All), cursor::MoveTo(0, 0))?;
+        println!(
+            "{}",
+            format!(
+                "Every {}s: {}    {}    (q to quit)",
+                interval.as_secs(),
+                command,
+                clock::format_utc(clock::now_secs())
+            )
+            .dimmed()
+        );
+        println!();
+        match &result {
+            Ok(output) => print!("{}", output.combined()),
+            Err(e) => eprintln!("{}", format!("❌ {}", e).red()),
+        }
+        stdout.flush()?;
+
+        // Wait out the interval, but react to q right away
+        terminal::enable_raw_mode()?;
+        let raw_mode = RawMode;
+        let deadline = std::time::Instant::now() + interval;
+        let quit = loop {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() || !event::poll(remaining)? {
+                break false;
+            }
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c) {
+                    break true;
+                }
+            }
+        };
+        drop(raw_mode);
+        if quit {
+            return Ok(());
+        }
+    }
+}
+
+/// The checks and rewrites every way of running a suggestion goes through: the
+/// blocklist, confirming catastrophic commands, the syntax check, then the sudo
+/// and trash rewrites. Returns the command to run, or None if it was blocked or
+/// the user cancelled.
+fn prepare_command(
+    suggestion: &CommandSuggestion,
+    options: &AskOptions,
+    settings: Option<&Settings>,
+    blocklist: &[Regex],
+) -> io::Result<Option<String>> {
+    if blocklist.iter().any(|pattern| pattern.is_match(&suggestion.command)) {
+        eprintln!("{}", "⛔ This command is blocked by policy.".red());
+        return Ok(None);
+    }
+
+    // Catastrophic commands always confirm, even with --yes or --no-severity-gate,
+    // unless the user trusts them through allow_patterns
+    if let Some(reason) = safety::catastrophic_reason(&suggestion.command, settings) {
+        eprintln!("{}", format!("🛑 This command {}.", reason).red().bold());
+        if !confirm_run(suggestion, settings)? {
+            println!("{}", "Cancelled.".yellow());
+            return Ok(None);
+        }
+    } else if let Some(reason) = safety::allowed_catastrophic_reason(&suggestion.command, settings) {
+        eprintln!("{}", format!("⚠️  Running without confirmation because allow_patterns trusts it: this command {}.", reason).yellow());
+    }
+
+    // Malformed model output fails cryptically in the shell; say what's wrong up front
+    let syntax_warnings = safety::check_shell_syntax(&suggestion.command);
+    if !syntax_warnings.is_empty() {
+        for warning in &syntax_warnings {
+            eprintln!("{}", format!("⚠️  Possible syntax error: {}", warning).yellow());
+        }
+        if !options.yes && io::stdin().is_terminal() {
+            print!("{} ", "Run it anyway? (y/N):".bold());
+            io::stdout().flush()?;
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            let input = input.trim();
+            if !input.eq_ignore_ascii_case("y") && !input.eq_ignore_ascii_case("yes") {
+                println!("{}", "Cancelled.".yellow());
+                return Ok(None);
+            }
+        }
+    }
+
+    let mut command = suggestion.command.clone();
+    if let Some(stripped) = safety::strip_redundant_sudo(&command, settings) {
+        println!("{}", format!("🔓 Already root, running without sudo: {}", stripped).cyan());
+        command = stripped;
+    }
+    if settings.is_some_and(|s| s.use_trash) {
+        if let Some((reversible, destination)) = safety::make_reversible(&command) {
+            println!("{}", format!("♻️  Moving to {} instead of deleting: {}", destination, reversible).cyan());
+            command = reversible;
+        }
+    }
+    Ok(Some(command))
+}
+
+/// What `--explain-errors` / "Explain error" sends to the model.
+struct RunFailure {
+    command: String,
+    exit_code: Option<i32>,
+    stderr: String,
+}
+
+async fn run_once(
+    suggestion: &CommandSuggestion,
+    options: &AskOptions,
+    settings: Option<&Settings>,
+    blocklist: &[Regex],
+) -> io::Result<Option<RunFailure>> {
+    let Some(command) = prepare_command(suggestion, options, settings, blocklist)? else {
+        return Ok(None);
+    };
+
+    let extra_interactive = settings.map(|s| s.interactive_programs.as_slice()).unwrap_or_default();
+    let wrapper = exec_wrapper(settings);
+    // What actually ran, for the log, last_run and error diagnosis
+    let ran = match &options.exec_pipe {
+        Some(downstream) => format!("{} | {}", command, downstream),
+        None => command.clone(),
+    };
+    let result = if let Some(downstream) = &options.exec_pipe {
+        println!("{}", format!("🔗 Piping into: {}", downstream).dimmed());
+        command_executor::execute_piped(&command, downstream, options.cwd.as_deref(), wrapper).await
+    } else if command_executor::is_interactive(&command, extra_interactive) {
+        command_executor::execute_interactive(&command, options.cwd.as_deref(), wrapper).await
+    } else {
+        command_executor::execute_command(&command, options.cwd.as_deref(), wrapper).await
+    };
+
+    if let Some(log_path) = &options.log_output {
+        if let Err(e) = command_executor::append_log(log_path, &ran, &result) {
+            eprintln!("{}", format!("⚠️  {}", e).yellow());
+        }
+    }
+
+    if let Ok(output) = &result {
+        if let Err(e) = last_run::record(&ran, output, settings) {
+            eprintln!("{}", format!("⚠️  {}", e).yellow());
+        }
+    }
+
+    let failure = match &result {
+        Ok(output) if output.status.success() => None,
+        Ok(output) => Some(RunFailure {
+            command: ran.clone(),
+            exit_code: output.status.code(),
+            stderr: output.stderr.clone(),
+        }),
+        Err(e) => Some(RunFailure {
+            command: ran.clone(),
+            exit_code: None,
+            stderr: e.clone(),
+        }),
+    };
+
+    match result.and_then(|output| output.into_result()) {
+        Ok(output) => {
+            if !output.trim().is_empty() {
+                // trim so the interactive path always ends on exactly one newline
+                println!();
+                let table = settings.filter(|s| s.table_output).and_then(|_| render_table(&output));
+                print_paged(table.as_deref().unwrap_or(output.trim_end()), options, settings);
 
-    let suggestion = match get_command_suggestion(question).await {
-        Ok(cmd) => cmd,
+                let threshold = settings.and_then(|s| s.output_filter_lines);
+                let long = threshold.is_some_and(|lines| output.lines().count() > lines);
+                if long && !options.yes && io::stdin().is_terminal() {
+                    filter_output(&output)?;
+                }
+            } else if failure.is_none() {
+                println!("{}", "✅ Done!".green());
+            }
+        }
         Err(e) => {
-            eprintln!("{}", format!("\n❌ Error: {}", e).red());
-            return Err(io::Error::new(io::ErrorKind::Other, e));
+            eprintln!("{}", format!("❌ Error: {}", e).red());
         }
+    }
+
+    if failure.is_none() {
+        report_directory_change(&command, options);
+    }
+
+    Ok(failure)
+}
+
+// cd only changes tella's subshell. A shell wrapper that sets $TELLA_CD_FILE gets
+// the directory written there to cd into; otherwise say the change won't stick.
+fn report_directory_change(command: &str, options: &AskOptions) {
+    let Some(target) = command_executor::cd_target(command, options.cwd.as_deref()) else {
+        return;
     };
 
-    // let elapsed = start.elapsed();
-    // eprintln!("🔍 Debug: API call took {:?}", elapsed);
+    match std::env::var_os("TELLA_CD_FILE") {
+        Some(file) => match std::fs::write(&file, target.to_string_lossy().as_bytes()) {
+            Ok(()) => println!("{}", format!("📂 Your shell will change to {}", target.display()).dimmed()),
+            Err(e) => eprintln!("{}", format!("⚠️  Failed to write TELLA_CD_FILE: {}", e).yellow()),
+        },
+        None => eprintln!(
+            "{}",
+            format!(
+                "⚠️  The change to {} won't persist: tella runs commands in a subshell. See \"Changing directory\" in the README.",
+                target.display()
+            )
+            .yellow()
+        ),
+    }
+}
 
-    // signal spinner thread to stop
-    dot_handle.store(true, std::sync::atomic::Ordering::SeqCst);
-    // give spinner a moment to clear the line
-    thread::sleep(Duration::from_millis(50));
-    print!("\r                    \r");
-    io::Write::flush(&mut io::stdout())?;
+// With --explain-errors the diagnosis is automatic; otherwise it's offered on a terminal.
+// Returns the fix to run, if the user picked it.
+async fn offer_diagnosis(
+    failure: &RunFailure,
+    options: &AskOptions,
+    settings: Option<&Settings>,
+) -> io::Result<Option<CommandSuggestion>> {
+    let interactive = io::stdin().is_terminal();
+    if !options.explain_errors {
+        if !interactive || options.yes {
+            return Ok(None);
+        }
+        println!();
+        let menu = MenuSelector::new()
+            .add_option("Explain error", "")
+            .add_option("Done", "");
+        if menu.show()? != 0 {
+            return Ok(None);
+        }
+    }
 
-    if suggestion.command == "ERROR" || suggestion.command == "no command returned" {
-        eprintln!("{}", suggestion.description.red());
-        eprintln!("{}", suggestion.explanation.yellow());
-        return Ok(());
+    let spinner = print_animated_dots(thinking_message(settings));
+    let result = diagnose_error(&failure.command, failure.exit_code, &failure.stderr, &options.prompt_context()).await;
+    spinner.stop();
+
+    let fix = match result {
+        Ok(fix) => fix,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Ok(None);
+        }
+    };
+
+    println!("\n{}", "🩺 Diagnosis".bold().cyan());
+    println!("{}", render_explanation(&fix.explanation));
+
+    if fix.command.trim().is_empty() || fix.command == "no command returned" {
+        return Ok(None);
     }
 
-    // Load settings to get output preferences
+    println!("\n{}", "Suggested fix:".bold());
+    display_suggestion(&fix, settings.map(|s| &s.output_settings));
+    if !interactive {
+        return Ok(None);
+    }
+
+    println!();
+    let menu = MenuSelector::new()
+        .add_option("Run fix", "")
+        .add_option("Done", "");
+    if menu.show()? != 0 || !confirm_menu_run(&fix, settings)? {
+        return Ok(None);
+    }
+    Ok(Some(fix))
+}
+
+/// With table_output: CSV or TSV output as an aligned table, header first.
+/// None when the output doesn't look tabular (under two rows, or rows with
+/// differing column counts), so it's printed untouched.
+fn render_table(output: &str) -> Option<String> {
+    let lines: Vec<&str> = output.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.len() < 2 {
+        return None;
+    }
+
+    let delimiter = ['\t', ','].into_iter().find(|delimiter| lines.iter().all(|line| line.contains(*delimiter)))?;
+    let rows: Vec<Vec<String>> = lines.iter().map(|line| split_fields(line, delimiter)).collect();
+    let columns = rows[0].len();
+    if columns < 2 || rows.iter().any(|row| row.len() != columns) {
+        return None;
+    }
+
+    let mut widths: Vec<usize> = (0..columns)
+        .map(|i| rows.iter().map(|row| row[i].chars().count()).max().unwrap_or(0))
+        .collect();
+
+    // Shrink the widest column until the table fits, down to a readable minimum
+    let terminal_width = crossterm::terminal::size().map_or(100, |(cols, _)| cols as usize);
+    let separators = (columns - 1) * 3;
+    while widths.iter().sum::<usize>() + separators > terminal_width {
+        let (widest, width) = widths.iter().copied().enumerate().max_by_key(|(_, width)| *width)?;
+        if width <= 8 {
+            break;
+        }
+        widths[widest] = width - 1;
+    }
+
+    let format_row = |row: &[String]| -> String {
+        row.iter()
+            .zip(&widths)
+            .map(|(cell, width)| {
+                let cell = if cell.chars().count() > *width {
+                    format!("{}…", cell.chars().take(width - 1).collect::<String>())
+                } else {
+                    cell.clone()
+                };
+                format!("{:<width$}", cell, width = width)
+            })
+            .collect::<Vec<_>>()
+            .join(" │ ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut table = vec![format_row(&rows[0]).bold().to_string()];
+    table.push(widths.iter().map(|width| "─".repeat(*width)).collect::<Vec<_>>().join("─┼─").dimmed().to_string());
+    table.extend(rows[1..].iter().map(|row| format_row(row)));
+    Some(table.join("\n"))
+}
+
+// Splits a CSV/TSV line; double-quoted fields may contain the delimiter
+fn split_fields(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+// Inline grep over captured output; an empty pattern ends the loop
+fn filter_output(output: &str) -> io::Result<()> {
+    loop {
+        println!();
+        let menu = MenuSelector::new()
+            .add_option("Filter output", "")
+            .add_option("Done", "");
+        if menu.show()? != 0 {
+            return Ok(());
+        }
+
+        print!("{} ", "Filter (text or regex, empty to stop):".bold());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let term = input.trim();
+        if term.is_empty() {
+            return Ok(());
+        }
+
+        let pattern = RegexBuilder::new(term)
+            .case_insensitive(true)
+            .build()
+            .or_else(|_| RegexBuilder::new(&regex::escape(term)).case_insensitive(true).build())
+            .map_err(io::Error::other)?;
+
+        let matches: Vec<&str> = output.lines().filter(|line| pattern.is_match(line)).collect();
+        println!();
+        for line in &matches {
+            println!("{}", line);
+        }
+        println!("{}", format!("{} of {} lines match", matches.len(), output.lines().count()).dimmed());
+    }
+}
+
+// History is best-effort; a failed write should never block the suggestion
+fn record_history(question: &str, suggestion: &CommandSuggestion, options: &AskOptions, settings: Option<&Settings>) {
+    if let Err(e) = history::record(question, suggestion, &options.tags, settings) {
+        eprintln!("{}", format!("⚠️  {}", e).yellow());
+    }
+}
+
+pub fn print_history(entries: &[HistoryEntry]) {
     let settings = Settings::load().ok();
     let output_settings = settings.as_ref().map(|s| &s.output_settings);
 
-    // Display command if enabled
-    if output_settings.map_or(true, |o| o.show_command) {
-        println!("{}", suggestion.command.bold().yellow());
-    }
-    
-    // Display severity and description if enabled
-    if output_settings.map_or(true, |o| o.show_severity || o.show_description) {
-        let severity_display = match suggestion.severity.as_str() {
-            "safe" => "🟢 SAFE".green(),
-            "warning" => "🟡 WARNING".yellow(),
-            "dangerous" => "🔴 DANGEROUS".red(),
-            _ => "⚪ UNKNOWN".normal(),
-        };
-        
-        if output_settings.map_or(true, |o| o.show_severity) {
-            if output_settings.map_or(true, |o| o.show_description) {
-                println!("{}", format!("{} - {}", severity_display, suggestion.description).dimmed());
+    if entries.is_empty() {
+        println!("{}", "No history entries found.".yellow());
+        return;
+    }
+
+    for entry in entries {
+        println!("{} {}", clock::format_utc(entry.timestamp).dimmed(), entry.question.bold());
+        println!("  {} {}", severity_label(&entry.severity, output_settings), entry.command.yellow());
+        if !entry.tags.is_empty() {
+            println!("  {}", format!("#{}", entry.tags.join(" #")).cyan());
+        }
+    }
+}
+
+// Unknown severities rank with warnings
+fn severity_rank(severity: &str) -> u8 {
+    match severity {
+        "safe" => 0,
+        "dangerous" => 2,
+        _ => 1,
+    }
+}
+
+// min_severity_display is display-only; auto-run and confirmation ignore it
+fn severity_displayed(severity: &str, output_settings: &OutputSettings) -> bool {
+    output_settings
+        .min_severity_display
+        .as_deref()
+        .is_none_or(|min| severity_rank(severity) >= severity_rank(min))
+}
+
+// --yes only runs without asking up to auto_run_max_severity (default "warning")
+fn within_auto_run_threshold(suggestion: &CommandSuggestion, settings: Option<&Settings>) -> bool {
+    let max_severity = settings
+        .and_then(|s| s.auto_run_max_severity.as_deref())
+        .unwrap_or("warning");
+    severity_rank(&suggestion.severity) <= severity_rank(max_severity)
+}
+
+// Picking Run is confirmation enough, unless dangerous_confirm_type asks for typing
+fn confirm_menu_run(suggestion: &CommandSuggestion, settings: Option<&Settings>) -> io::Result<bool> {
+    if suggestion.severity != "dangerous" || confirm_type(settings) == "menu" {
+        return Ok(true);
+    }
+    confirm_run(suggestion, settings)
+}
+
+// "menu" (default), "type-yes" or "type-command"
+fn confirm_type(settings: Option<&Settings>) -> &str {
+    settings
+        .and_then(|s| s.dangerous_confirm_type.as_deref())
+        .unwrap_or("menu")
+}
+
+/// Asks before running a risky command. With `dangerous_confirm_type` set to
+/// "type-yes" or "type-command" a single keypress is not enough.
+fn confirm_run(suggestion: &CommandSuggestion, settings: Option<&Settings>) -> io::Result<bool> {
+    let program = suggestion
+        .command
+        .split_whitespace()
+        .find(|token| *token != "sudo")
+        .unwrap_or_default();
+    let (prompt, expected) = match confirm_type(settings) {
+        "type-yes" => ("Type 'yes' to run this command:".to_string(), Some("yes")),
+        "type-command" => (format!("Type '{}' to run this command:", program), Some(program)),
+        _ => ("Run this command anyway? (y/N):".to_string(), None),
+    };
+
+    print!("{} {} ", severity_label(&suggestion.severity, settings.map(|s| &s.output_settings)), prompt.bold());
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let input = input.trim();
+    Ok(match expected {
+        // Exact match: typing is the point
+        Some(expected) => input == expected,
+        None => input.eq_ignore_ascii_case("y") || input.eq_ignore_ascii_case("yes"),
+    })
+}
+
+const DEFAULT_OUTPUT_ORDER: &[&str] = &["command", "severity", "description"];
+
+fn display_suggestion(suggestion: &CommandSuggestion, output_settings: Option<&OutputSettings>) {
+    let order: Vec<&str> = match output_settings {
+        Some(o) if !o.output_order.is_empty() => o.output_order.iter().map(|f| f.as_str()).collect(),
+        _ => DEFAULT_OUTPUT_ORDER.to_vec(),
+    };
+
+    let enabled = |field: &&str| match *field {
+        "command" => output_settings.is_none_or(|o| o.show_command),
+        "severity" => output_settings.is_none_or(|o| o.show_severity && severity_displayed(&suggestion.severity, o)),
+        "description" => output_settings.is_none_or(|o| o.show_description),
+        "explanation" => output_settings.is_none_or(|o| o.show_explanation) && !suggestion.explanation.is_empty(),
+        _ => false,
+    };
+
+    let mut fields = order.into_iter().filter(enabled).peekable();
+    while let Some(field) = fields.next() {
+        match field {
+            "command" => {
+                print_command(&suggestion.command, output_settings.is_some_and(|o| o.highlight_command));
+                if !suggestion.rationale.is_empty() {
+                    println!("{}", format!("↳ {}", suggestion.rationale).italic().dimmed());
+                }
+            }
+            "severity" => {
+                let severity_display = severity_label(&suggestion.severity, output_settings);
+                // Adjacent severity and description share a line
+                if fields.peek() == Some(&"description") {
+                    fields.next();
+                    println!("{}", format!("{} - {}", severity_display, suggestion.description).dimmed());
+                } else {
+                    println!("{}", severity_display);
+                }
+            }
+            "description" => println!("{}", suggestion.description.dimmed()),
+            "explanation" => println!("{}", render_explanation(&suggestion.explanation)),
+            _ => {}
+        }
+    }
+}
+
+/// Prints `text`, through `$PAGER` (or `less -R`) when paging is on, stdout is a
+/// terminal and the text is taller than the screen. Falls back to printing
+/// directly when no pager can be started.
+fn print_paged(text: &str, options: &AskOptions, settings: Option<&Settings>) {
+    let enabled = options.pager || settings.is_some_and(|s| s.use_pager);
+    let rows = crossterm::terminal::size().map_or(24, |(_, rows)| rows as usize);
+    if !enabled || !io::stdout().is_terminal() || text.lines().count() < rows {
+        println!("{}", text);
+        return;
+    }
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(target_os = "windows") { "more".to_string() } else { "less -R".to_string() });
+    let mut words = pager.split_whitespace();
+    let Some(program) = words.next() else {
+        println!("{}", text);
+        return;
+    };
+
+    let child = std::process::Command::new(program)
+        .args(words)
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => {
+            println!("{}", text);
+            return;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's fine
+        let _ = writeln!(stdin, "{}", text);
+    }
+    let _ = child.wait();
+}
+
+// Bullet and step lines from --explain-format get indented; prose passes through
+fn render_explanation(explanation: &str) -> String {
+    explanation
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let is_step = trimmed
+                .split_once(". ")
+                .is_some_and(|(number, _)| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()));
+            if let Some(item) = trimmed.strip_prefix("- ").or_else(|| trimmed.strip_prefix("* ")) {
+                format!("  • {}", item)
+            } else if is_step {
+                format!("  {}", trimmed)
             } else {
-                println!("{}", severity_display);
+                line.to_string()
             }
-        } else if output_settings.map_or(true, |o| o.show_description) {
-            println!("{}", suggestion.description.dimmed());
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Multi-line commands (heredocs, `\` continuations) get a box so it's clear the
+// whole block runs. Only the display changes; execution gets the exact text.
+fn print_command(command: &str, highlight: bool) {
+    let render = |line: &str| {
+        if highlight {
+            ui::highlight_command(line)
+        } else {
+            line.bold().yellow().to_string()
         }
+    };
+
+    let lines: Vec<&str> = command.trim_end().lines().collect();
+    if lines.len() <= 1 {
+        println!("{}", render(command));
+        return;
+    }
+
+    println!("{}", "┌─ multi-line command".dimmed());
+    for line in &lines {
+        println!("{} {}", "│".dimmed(), render(line));
+    }
+    println!("{}", format!("└─ Run executes all {} lines as one script", lines.len()).dimmed());
+}
+
+// Invalid patterns are matched literally so a typo never silently disables the block
+fn compile_blocklist(settings: &Settings) -> Vec<Regex> {
+    settings
+        .blocked_commands
+        .iter()
+        .map(|pattern| {
+            Regex::new(pattern).unwrap_or_else(|e| {
+                eprintln!("{}", format!("⚠️  Invalid blocked_commands pattern '{}': {}. Matching it literally.", pattern, e).yellow());
+                Regex::new(&regex::escape(pattern)).expect("escaped pattern is valid")
+            })
+        })
+        .collect()
+}
+
+fn severity_label(severity: &str, output_settings: Option<&OutputSettings>) -> ColoredString {
+    let (emoji, color) = severity_style(severity, output_settings);
+    let label = match emoji.is_empty() {
+        true => severity_text(severity).to_string(),
+        false => format!("{} {}", emoji, severity_text(severity)),
+    };
+    match color {
+        Some(color) => label.color(color),
+        None => label.normal(),
     }
+}
+
+// The built-in emoji and color, overridden per level by severity_display
+fn severity_style(severity: &str, output_settings: Option<&OutputSettings>) -> (String, Option<Color>) {
+    let (level, emoji, color) = match severity {
+        "safe" => ("safe", "🟢", Some(Color::Green)),
+        "warning" => ("warning", "🟡", Some(Color::Yellow)),
+        "dangerous" => ("dangerous", "🔴", Some(Color::Red)),
+        _ => ("unknown", "⚪", None),
+    };
+    let Some(custom) = output_settings.and_then(|o| o.severity_display.get(level)) else {
+        return (emoji.to_string(), color);
+    };
+    (
+        custom.emoji.clone().unwrap_or_else(|| emoji.to_string()),
+        custom.color.as_deref().and_then(|name| name.parse().ok()).or(color),
+    )
+}
+
+pub fn print_severity_legend() {
+    let settings = Settings::load().ok();
+    let output_settings = settings.as_ref().map(|s| &s.output_settings);
 
+    println!("{}", "Severity legend".bold().cyan());
+    println!("{}", "━".repeat(50));
+    let rows = [
+        ("safe", "Read-only or low-risk. Runs as soon as you pick Run."),
+        ("warning", "Modifies files or settings. Review it before picking Run."),
+        ("dangerous", "Can delete data or change the system. Double-check before picking Run; --yes asks for confirmation (set dangerous_confirm_type to make Run ask too)."),
+        ("", "The model did not classify the command. Treat it as a warning."),
+    ];
+    for (severity, meaning) in rows {
+        println!("  {}", severity_label(severity, output_settings));
+        println!("     {}", meaning.dimmed());
+    }
     println!();
-    loop {
-        let mut menu = MenuSelector::new()
-            .add_option("Run", "");
-        
-        // Only add Explain option if explanation is enabled
-        let explain_enabled = output_settings.map_or(true, |o| o.show_explanation);
-        if explain_enabled {
-            menu = menu.add_option("Explain", "");
+    println!(
+        "  {}",
+        "Commands that could wipe a disk or the system (rm -rf /, mkfs, dd to a device, ...) always ask first, unless allow_patterns trusts them.".dimmed()
+    );
+}
+
+enum AlternativeChoice {
+    One(CommandSuggestion),
+    // "Run until one succeeds" already ran them
+    RanAll,
+    Back,
+}
+
+// Follow-up request for a few alternatives; returns what the user picked
+async fn pick_alternative(
+    question: &str,
+    options: &AskOptions,
+    settings: Option<&Settings>,
+) -> io::Result<AlternativeChoice> {
+    let spinner = print_animated_dots(thinking_message(settings));
+    let result = get_command_suggestions(question, &options.prompt_context(), 3).await;
+    spinner.stop();
+
+    match result {
+        Ok(alternatives) => choose_suggestion(question, &alternatives, options, settings).await,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            Ok(AlternativeChoice::Back)
         }
-        
-        let menu = menu.add_option("Stop", "");
-        let selected = menu.show()?;
+    }
+}
 
-        match selected {
-            0 => {
-                match command_executor::execute_command(&suggestion.command).await {
-                    Ok(output) => {
-                        if !output.trim().is_empty() {
-                            println!("\n{}", output);
-                        } else {
-                            println!("{}", "✅ Done!".green());
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("{}", format!("❌ Error: {}", e).red());
-                    }
-                }
-                break;
+async fn choose_suggestion(
+    question: &str,
+    alternatives: &[CommandSuggestion],
+    options: &AskOptions,
+    settings: Option<&Settings>,
+) -> io::Result<AlternativeChoice> {
+    let output_settings = settings.map(|s| &s.output_settings);
+    for (i, alternative) in alternatives.iter().enumerate() {
+        println!("{}", format!("{}.", i + 1).cyan());
+        display_suggestion(alternative, output_settings);
+        println!();
+    }
+
+    let labels: Vec<String> = (1..=alternatives.len()).map(|i| format!("Option {}", i)).collect();
+    let menu = labels
+        .iter()
+        .fold(MenuSelector::new(), |menu, label| menu.add_option(label, ""))
+        .add_option("Run until one succeeds", "")
+        .add_option("Back", "");
+    let selected = menu.show()?;
+
+    if selected == alternatives.len() {
+        run_until_success(question, alternatives, options, settings).await?;
+        return Ok(AlternativeChoice::RanAll);
+    }
+    Ok(alternatives
+        .get(selected)
+        .cloned()
+        .map_or(AlternativeChoice::Back, AlternativeChoice::One))
+}
+
+// Tries each alternative in order and stops at the first that exits 0. Blocked
+// and catastrophic commands are skipped; dangerous ones still confirm first.
+async fn run_until_success(
+    question: &str,
+    alternatives: &[CommandSuggestion],
+    options: &AskOptions,
+    settings: Option<&Settings>,
+) -> io::Result<()> {
+    let blocklist = settings.map(compile_blocklist).unwrap_or_default();
+
+    for (i, alternative) in alternatives.iter().enumerate() {
+        let command = &alternative.command;
+        println!("{}", format!("▶ Trying option {}: {}", i + 1, command).cyan());
+
+        if let Some(reason) = safety::catastrophic_reason(command, settings) {
+            eprintln!("{}", format!("  🛑 Skipping: this command {}.", reason).red());
+            continue;
+        }
+        if !confirm_menu_run(alternative, settings)? {
+            println!("{}", "  Skipped.".yellow());
+            continue;
+        }
+        let Some(command) = prepare_command(alternative, options, settings, &blocklist)? else {
+            continue;
+        };
+
+        let extra_interactive = settings.map(|s| s.interactive_programs.as_slice()).unwrap_or_default();
+        let wrapper = exec_wrapper(settings);
+        let result = if command_executor::is_interactive(&command, extra_interactive) {
+            command_executor::execute_interactive(&command, options.cwd.as_deref(), wrapper).await
+        } else {
+            command_executor::execute_command(&command, options.cwd.as_deref(), wrapper).await
+        };
+        if let Some(log_path) = &options.log_output {
+            if let Err(e) = command_executor::append_log(log_path, &command, &result) {
+                eprintln!("{}", format!("⚠️  {}", e).yellow());
             }
-            1 if explain_enabled => {
-                // Show explanation
-                println!("\n{}", suggestion.explanation);
-                println!();
+        }
+        if let Ok(output) = &result {
+            if let Err(e) = last_run::record(&command, output, settings) {
+                eprintln!("{}", format!("⚠️  {}", e).yellow());
             }
-            _ => {
-                println!("{}", "Goodbye!".yellow());
-                break;
+        }
+
+        match result {
+            Ok(output) if output.status.success() => {
+                let combined = output.combined();
+                if !combined.trim().is_empty() {
+                    println!("\n{}", combined.trim_end());
+                }
+                println!("{}", format!("✅ Option {} worked: {}", i + 1, command).green());
+                record_history(question, alternative, options, settings);
+                return Ok(());
             }
+            Ok(output) => {
+                let code = output.status.code().map_or("a signal".to_string(), |code| code.to_string());
+                println!("{}", format!("  ✗ Failed (exit {})", code).dimmed());
+            }
+            Err(e) => println!("{}", format!("  ✗ {}", e).dimmed()),
         }
     }
 
+    eprintln!("{}", "❌ None of the alternatives succeeded.".red());
     Ok(())
 }
 
-fn print_animated_dots() -> std::sync::Arc<std::sync::atomic::AtomicBool> {
+// Quiet/JSON modes write only the result to stdout so it can be captured with $(...)
+async fn handle_non_interactive(question: &str, options: &AskOptions) -> io::Result<()> {
+    let as_list = options.alternatives.is_some_and(|count| count > 1) && !options.first;
+
+    let suggestions = match fetch_suggestions(question, options).await {
+        Ok(suggestions) => suggestions,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            if options.format == Some(OutputFormat::Jsonl) {
+                write_jsonl_line(serde_json::json!({ "question": question, "error": e }))?;
+            }
+            return Err(io::Error::other(e));
+        }
+    };
+
+    // --first picks the top-ranked alternative
+    let chosen = if options.first { &suggestions[..1] } else { &suggestions[..] };
+
+    let trailing_newline = !options.no_trailing_newline;
+
+    // Every output format is recorded alike, so scripted runs show up in history too
+    let settings = Settings::load().ok();
+    let is_command = |s: &CommandSuggestion| s.command != "ERROR" && s.command != "no command returned";
+    for suggestion in chosen.iter().filter(|s| is_command(s)) {
+        record_history(question, suggestion, options, settings.as_ref());
+    }
+
+    if options.format == Some(OutputFormat::Markdown) {
+        let output_settings = settings.as_ref().map(|s| &s.output_settings);
+        let markdown: Vec<String> = chosen.iter().map(|s| render_markdown(s, output_settings)).collect();
+        return write_output(&markdown.join("\n\n---\n\n"), trailing_newline);
+    }
+
+    // One line per question, so a batch can be matched up line by line
+    if options.format == Some(OutputFormat::Jsonl) {
+        let mut line = if as_list {
+            serde_json::json!({ "alternatives": chosen })
+        } else {
+            serde_json::to_value(&chosen[0]).map_err(io::Error::other)?
+        };
+        line["question"] = serde_json::Value::from(question);
+        return write_jsonl_line(line);
+    }
+
+    if options.json || options.format == Some(OutputFormat::Json) {
+        let json = if as_list {
+            serde_json::to_string_pretty(chosen)
+        } else {
+            serde_json::to_string_pretty(&chosen[0])
+        }
+        .map_err(io::Error::other)?;
+        return write_output(&json, trailing_newline);
+    }
+
+    let suggestion = &chosen[0];
+    if !is_command(suggestion) {
+        eprintln!("{}", suggestion.description.red());
+        return Err(io::Error::other("no command returned"));
+    }
+
+    let commands: Vec<&str> = chosen.iter().map(|s| s.command.as_str()).collect();
+    write_output(&commands.join("\n"), trailing_newline)
+}
+
+fn render_markdown(suggestion: &CommandSuggestion, output_settings: Option<&OutputSettings>) -> String {
+    let language = match shell_type() {
+        "PowerShell" => "powershell",
+        "bash" => "bash",
+        _ => "sh",
+    };
+
+    let mut markdown = format!("```{}\n{}\n```", language, suggestion.command);
+
+    if !suggestion.description.is_empty() {
+        markdown.push_str(&format!("\n\n{}", suggestion.description));
+    }
+
+    let show_severity = output_settings.is_none_or(|o| o.show_severity);
+    if show_severity && !suggestion.severity.is_empty() {
+        markdown.push_str(&format!("\n\n> **Severity:** {}", suggestion.severity));
+        if !suggestion.severity_description.is_empty() {
+            markdown.push_str(&format!(" — {}", suggestion.severity_description));
+        }
+    }
+
+    markdown
+}
+
+// One compact object per line, flushed right away so line-oriented consumers
+// get each question's result as soon as it's ready
+fn write_jsonl_line(line: serde_json::Value) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{}", line)?;
+    stdout.flush()
+}
+
+fn write_output(text: &str, trailing_newline: bool) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    stdout.write_all(text.as_bytes())?;
+    if trailing_newline {
+        stdout.write_all(b"\n")?;
+    }
+    stdout.flush()
+}
+
+// Animated output only makes sense on a real terminal; logs get a single line instead
+fn spinner_enabled() -> bool {
+    io::stdout().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Running spinner. Dropping it (including on early return or panic) stops
+/// the thread and waits for it to clear the line.
+struct Spinner {
+    stop_flag: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Spinner {
+    fn stop(self) {}
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop_flag.store(true, std::sync::atomic::Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn thinking_message(settings: Option<&Settings>) -> String {
+    settings
+        .and_then(|s| s.thinking_message.clone())
+        .filter(|message| !message.trim().is_empty())
+        .unwrap_or_else(|| "Thinking...".to_string())
+}
+
+/// Runs `request` until it finishes or Esc is pressed, which drops it; `None`
+/// means it was cancelled. Without a terminal the request just runs.
+/// The terminal is in raw mode meanwhile, so warnings are held back until
+/// `api::release_warnings`.
+async fn cancellable<T>(request: impl std::future::Future<Output = T>) -> io::Result<Option<T>> {
+    if !io::stdin().is_terminal() || crossterm::terminal::enable_raw_mode().is_err() {
+        return Ok(Some(request.await));
+    }
+    let _raw_mode = RawMode;
+    // Until the caller has stopped the spinner and released them
+    api::hold_warnings();
+
+    tokio::select! {
+        result = request => Ok(Some(result)),
+        pressed = escape_pressed() => pressed.map(|()| None),
+    }
+}
+
+// Raw mode keeps Ctrl-C from interrupting, so it cancels like Esc
+async fn escape_pressed() -> io::Result<()> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    loop {
+        while event::poll(Duration::ZERO)? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (key.code == KeyCode::Esc || ctrl_c) {
+                    return Ok(());
+                }
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Leaves raw mode when dropped, so an early return or `?` can't leave the
+/// terminal in it.
+struct RawMode;
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        crossterm::terminal::disable_raw_mode().ok();
+    }
+}
+
+// The end of the streamed text on one line, as much as fits after the spinner
+fn streamed_tail(preview: &str) -> String {
+    let width = crossterm::terminal::size().map_or(80, |(cols, _)| cols as usize).saturating_sub(3).max(10);
+    let flat = preview.split_whitespace().collect::<Vec<_>>().join(" ");
+    let skip = flat.chars().count().saturating_sub(width);
+    flat.chars().skip(skip).collect()
+}
+
+fn print_animated_dots(message: String) -> Spinner {
     let stop_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let stop_flag_clone = stop_flag.clone();
 
-    std::thread::spawn(move || {
+    if !spinner_enabled() {
+        eprintln!("{}", message);
+        return Spinner { stop_flag, thread: None };
+    }
+
+    let thread = std::thread::spawn(move || {
         let spinner = ['|', '/', '-', '\\'];
         let mut i = 0;
+        let mut stdout = io::stdout();
         while !stop_flag_clone.load(std::sync::atomic::Ordering::Relaxed) {
-            print!("\r{} Thinking...", spinner[i]);
-            io::Write::flush(&mut io::stdout()).ok();
+            // Once a reply streams in, its latest text replaces the message
+            let preview = stream_preview();
+            let text = match preview.trim().is_empty() {
+                true => message.clone(),
+                false => streamed_tail(&preview).dimmed().to_string(),
+            };
+            print!("\r{} {}", spinner[i], text);
+            crossterm::execute!(stdout, crossterm::terminal::Clear(crossterm::terminal::ClearType::UntilNewLine)).ok();
             thread::sleep(Duration::from_millis(100));
             i = (i + 1) % spinner.len();
         }
-        print!("\r                    \r"); // Clear the line
-        io::Write::flush(&mut io::stdout()).ok();
+        // Clear the line
+        crossterm::execute!(
+            stdout,
+            crossterm::cursor::MoveToColumn(0),
+            crossterm::terminal::Clear(crossterm::terminal::ClearType::CurrentLine)
+        )
+        .ok();
     });
 
-    stop_flag
+    Spinner {
+        stop_flag,
+        thread: Some(thread),
+    }
 }
 
@@ -0,0 +1,60 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// 2024-01-01T00:00:00Z; a clock reading earlier than this is certainly wrong
+const EARLIEST_PLAUSIBLE_SECS: u64 = 1_704_067_200;
+
+/// Seconds since `timestamp`. A timestamp in the future (the clock moved back
+/// since it was stored) counts as age 0, i.e. still fresh, never negative.
+pub fn age_secs(timestamp: u64) -> u64 {
+    now_secs().saturating_sub(timestamp)
+}
+
+/// Whether the system clock looks sane enough to trust for TTLs.
+pub fn is_plausible() -> bool {
+    now_secs() >= EARLIEST_PLAUSIBLE_SECS
+}
+
+/// A warning when the clock is obviously wrong, for the startup self-check.
+pub fn skew_warning() -> Option<String> {
+    if is_plausible() {
+        return None;
+    }
+    Some(format!(
+        "The system clock reads {}, which looks wrong; the cache is skipped until it's fixed.",
+        format_utc(now_secs())
+    ))
+}
+
+// RFC 3339 UTC timestamp without pulling in a date crate
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+
+    // civil-from-days (Howard Hinnant)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        (time % 3_600) / 60,
+        time % 60
+    )
+}
@@ -1,61 +1,625 @@
-use std::process::Command;
+use crate::clock;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::OnceLock;
 
-#[cfg(target_os = "windows")]
-pub async fn execute_command(command: &str) -> Result<String, String> {
-    let output = Command::new("powershell")
-        .arg("-Command")
-        .arg(command)
-        .output()
-        .map_err(|e| format!("Failed to execute command: {}", e))?;
+pub struct CommandOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+impl CommandOutput {
+    pub fn combined(&self) -> String {
+        if self.stderr.is_empty() {
+            self.stdout.clone()
+        } else {
+            format!("{}\n{}", self.stdout, self.stderr)
+        }
+    }
+
+    // A failing command that wrote to stderr is an error; otherwise show everything
+    pub fn into_result(self) -> Result<String, String> {
+        if !self.status.success() && !self.stderr.is_empty() {
+            return Err(format!("Error: {}", self.stderr));
+        }
+
+        Ok(self.combined())
+    }
+}
+
+/// Shells `--shell` accepts; anything but PowerShell is run as `<shell> -c`.
+pub const SHELLS: &[&str] = &["bash", "zsh", "sh", "ksh", "fish", "powershell"];
+
+static SHELL_OVERRIDE: OnceLock<&'static str> = OnceLock::new();
+
+/// `--shell`: use this shell for prompts and execution instead of the OS default.
+pub fn set_shell(name: &str) -> Result<(), String> {
+    let shell = SHELLS
+        .iter()
+        .find(|shell| shell.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("Unknown shell '{}'. Supported: {}", name, SHELLS.join(", ")))?;
+    let _ = SHELL_OVERRIDE.set(shell);
+    Ok(())
+}
+
+/// The shell commands run in: the `--shell` override, else the OS default.
+pub fn shell_program() -> &'static str {
+    if let Some(shell) = SHELL_OVERRIDE.get() {
+        return shell;
+    }
+    match env::consts::OS {
+        "windows" => "powershell",
+        "linux" => "bash",
+        // OpenBSD's /bin/sh is ksh anyway; say so so suggestions can use it
+        "openbsd" => "ksh",
+        // macOS, FreeBSD, NetBSD, DragonFly, illumos, ...
+        _ => "sh",
+    }
+}
+
+fn shell_command(command: &str) -> Command {
+    let shell = shell_program();
+    let mut cmd = Command::new(shell);
+    cmd.arg(shell_flag(shell)).arg(command);
+    cmd
+}
+
+fn shell_flag(shell: &str) -> &'static str {
+    if shell == "powershell" {
+        "-Command"
+    } else {
+        "-c"
+    }
+}
+
+/// The command to spawn, run through `wrapper` (the exec_wrapper setting) when
+/// set. A wrapper ending in `-c`/`-Command` (e.g. `docker run --rm -i img sh -c`)
+/// names its own shell and gets the command as its last argument; any other
+/// (e.g. `firejail --net=none`) gets `<shell> -c <command>` appended. The
+/// command is always one argv entry, so it's never re-quoted or re-split.
+fn wrapped_command(command: &str, wrapper: Option<&str>) -> Result<Command, String> {
+    let Some(wrapper) = wrapper.filter(|w| !w.trim().is_empty()) else {
+        return Ok(shell_command(command));
+    };
+
+    let words = split_words(wrapper).ok_or_else(|| format!("Invalid exec_wrapper (unbalanced quotes): {}", wrapper))?;
+    let (program, args) = words.split_first().ok_or("exec_wrapper is empty")?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if !matches!(args.last().map(String::as_str), Some("-c" | "-Command")) {
+        let shell = shell_program();
+        cmd.arg(shell).arg(shell_flag(shell));
+    }
+    cmd.arg(command);
+    Ok(cmd)
+}
+
+// Splits like a shell would for plain words, single and double quotes; None on
+// an unterminated quote
+fn split_words(text: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
 
-    if !output.status.success() {
-        if !stderr.is_empty() {
-            return Err(format!("Error: {}", stderr));
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
         }
     }
 
-    Ok(if stderr.is_empty() { stdout } else { format!("{}\n{}", stdout, stderr) })
+    if quote.is_some() {
+        return None;
+    }
+    if in_word {
+        words.push(word);
+    }
+    Some(words)
+}
+
+/// The directory a command starting with `cd <dir>` (alone or before `&&`, `;`
+/// or `||`) changes to, resolved against `cwd`. `cd -` and directories with
+/// variables aren't resolved.
+pub fn cd_target(command: &str, cwd: Option<&Path>) -> Option<PathBuf> {
+    let first = command.split([';', '&', '|']).next()?;
+    let words = split_words(first)?;
+    let dir = match words.as_slice() {
+        [cd] if cd == "cd" => return dirs::home_dir(),
+        [cd, dir] if cd == "cd" => dir,
+        _ => return None,
+    };
+    if dir == "-" || dir.contains('$') {
+        return None;
+    }
+
+    let path = match dir.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => dirs::home_dir()?.join(rest.trim_start_matches('/')),
+        _ => match cwd {
+            Some(cwd) => cwd.join(dir),
+            None => env::current_dir().ok()?.join(dir),
+        },
+    };
+    path.canonicalize().ok().filter(|path| path.is_dir())
 }
 
-#[cfg(target_os = "linux")]
-pub async fn execute_command(command: &str) -> Result<String, String> {
-    let output = Command::new("bash")
-        .arg("-c")
-        .arg(command)
+pub async fn execute_command(command: &str, cwd: Option<&Path>, wrapper: Option<&str>) -> Result<CommandOutput, String> {
+    let mut cmd = wrapped_command(command, wrapper)?;
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+
+    let output = cmd
         .output()
         .map_err(|e| format!("Failed to execute command: {}", e))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        status: output.status,
+    })
+}
+
+/// `--exec-pipe`: runs `command` and pipes its stdout into `downstream` (run
+/// through the shell too), like `command | downstream`. Unlike a shell pipeline,
+/// a failure in either stage is reported as that stage's. Only `command` goes
+/// through `wrapper`; the downstream program runs on the host.
+pub async fn execute_piped(command: &str, downstream: &str, cwd: Option<&Path>, wrapper: Option<&str>) -> Result<CommandOutput, String> {
+    let mut first = wrapped_command(command, wrapper)?;
+    let mut second = shell_command(downstream);
+    if let Some(dir) = cwd {
+        first.current_dir(dir);
+        second.current_dir(dir);
+    }
+
+    let mut first = first
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+    let pipe = first.stdout.take().ok_or("Failed to capture the command's output")?;
+
+    let spawned = second
+        .stdin(Stdio::from(pipe))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    // The builder holds our copy of the pipe; close it so the first stage sees
+    // EPIPE when the downstream program stops reading (e.g. `head -1`)
+    drop(second);
+    let second = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = first.kill();
+            let _ = first.wait();
+            return Err(format!("Failed to start '{}': {}", downstream, e));
+        }
+    };
+
+    // Drain the first stage's stderr on the side so neither stage blocks on a full pipe
+    let mut first_stderr = first.stderr.take();
+    let stderr_reader = std::thread::spawn(move || {
+        let mut stderr = Vec::new();
+        if let Some(pipe) = first_stderr.as_mut() {
+            let _ = std::io::Read::read_to_end(pipe, &mut stderr);
+        }
+        String::from_utf8_lossy(&stderr).to_string()
+    });
+
+    let output = second
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run '{}': {}", downstream, e))?;
+    let first_status = first.wait().map_err(|e| format!("Failed to execute command: {}", e))?;
+    let first_stderr = stderr_reader.join().unwrap_or_default();
+
+    // Same rule as CommandOutput::into_result: a failure that explains itself on stderr
+    if !first_status.success() && !first_stderr.trim().is_empty() {
+        return Err(format!("The command failed ({}): {}", first_status, first_stderr.trim_end()));
+    }
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    if !output.status.success() && !stderr.trim().is_empty() {
+        return Err(format!("'{}' failed ({}): {}", downstream, output.status, stderr.trim_end()));
+    }
+
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: first_stderr,
+        status: output.status,
+    })
+}
+
+/// Programs that need the terminal whatever their arguments; their output is not captured.
+const INTERACTIVE_PROGRAMS: &[&str] = &[
+    "less", "more", "most", "man", "vi", "vim", "nvim", "nano", "emacs", "top", "htop", "btop",
+    "watch", "tmux", "screen",
+];
+
+/// Programs that only need the terminal when they start a session: bare
+/// `python3` or `ssh host` does, `python3 script.py` or `ssh host uptime` runs
+/// something and exits, so its output is captured like any other command's.
+const SESSION_PROGRAMS: &[&str] = &["ssh", "mysql", "psql", "sqlite3", "python", "python3", "node", "irb"];
+
+// Options of the session programs that take the next word as their value
+const SSH_VALUE_OPTIONS: &[&str] = &[
+    "-B", "-b", "-c", "-D", "-E", "-e", "-F", "-I", "-i", "-J", "-L", "-l", "-m", "-O", "-o", "-p", "-Q", "-R", "-S", "-W", "-w",
+];
+const PYTHON_VALUE_OPTIONS: &[&str] = &["-W", "-X", "--check-hash-based-pycs"];
+const NODE_VALUE_OPTIONS: &[&str] = &["-r", "--require", "--import", "--loader"];
+const IRB_VALUE_OPTIONS: &[&str] = &["-r", "-I"];
+const SQLITE_VALUE_OPTIONS: &[&str] = &["-cmd", "-init", "-separator", "-newline", "-nullvalue", "-escape"];
 
-    if !output.status.success() {
-        if !stderr.is_empty() {
-            return Err(format!("Error: {}", stderr));
+/// True when the command's base program (after `sudo` and `VAR=value` prefixes)
+/// is a known interactive program or listed in `extra`, or a session program
+/// started without a script or command to run.
+pub fn is_interactive(command: &str, extra: &[String]) -> bool {
+    // Only the first program's own arguments, not what follows a pipe, `;` or `&&`
+    let first = command.split(['|', ';', '&']).next().unwrap_or_default();
+    let mut tokens = first
+        .split_whitespace()
+        .skip_while(|token| *token == "sudo" || token.contains('='));
+    let Some(program) = tokens.next().map(|token| token.rsplit(['/', '\\']).next().unwrap_or(token)) else {
+        return false;
+    };
+
+    if INTERACTIVE_PROGRAMS.contains(&program) || extra.iter().any(|p| p == program) {
+        return true;
+    }
+    if !SESSION_PROGRAMS.contains(&program) {
+        return false;
+    }
+
+    let args: Vec<&str> = tokens.collect();
+    starts_session(program, &args)
+}
+
+// Whether a session program given these arguments waits for the user to type
+fn starts_session(program: &str, args: &[&str]) -> bool {
+    // Input from a file or a here-doc: there is nothing left to type
+    if args.iter().any(|arg| arg.starts_with('<')) {
+        return false;
+    }
+    let has = |options: &[&str]| {
+        args.iter().any(|arg| options.iter().any(|option| arg == option || arg.starts_with(&format!("{}=", option))))
+    };
+
+    match program {
+        // `ssh host` logs in; `ssh host uptime` runs uptime, unless -t asks for a terminal
+        "ssh" => has(&["-t", "-tt"]) || positional_args(args, SSH_VALUE_OPTIONS).len() <= 1,
+        // The positional argument is a database, so only a query makes it a one-off
+        "mysql" => !has(&["-e", "--execute"]),
+        "psql" => !has(&["-c", "--command", "-f", "--file", "-l", "--list"]),
+        // `sqlite3 app.db` opens a prompt; `sqlite3 app.db "SELECT ..."` runs the query
+        "sqlite3" => positional_args(args, SQLITE_VALUE_OPTIONS).len() <= 1,
+        // -i keeps the prompt open after running the script or -c code
+        "python" | "python3" => {
+            let runs_code = has(&["-c", "-m"]) || !positional_args(args, PYTHON_VALUE_OPTIONS).is_empty();
+            has(&["-i"]) || !runs_code
+        }
+        "node" => {
+            let runs_code = has(&["-e", "--eval", "-p", "--print"]) || !positional_args(args, NODE_VALUE_OPTIONS).is_empty();
+            has(&["-i", "--interactive"]) || !runs_code
         }
+        // irb
+        _ => positional_args(args, IRB_VALUE_OPTIONS).is_empty(),
     }
+}
 
-    Ok(if stderr.is_empty() { stdout } else { format!("{}\n{}", stdout, stderr) })
+// The arguments that aren't options or the values of `value_options`
+fn positional_args<'a>(args: &[&'a str], value_options: &[&str]) -> Vec<&'a str> {
+    let mut positional = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+        } else if arg.starts_with('-') {
+            skip_value = value_options.contains(arg);
+        } else {
+            positional.push(*arg);
+        }
+    }
+    positional
 }
 
-#[cfg(target_os = "macos")]
-pub async fn execute_command(command: &str) -> Result<String, String> {
-    let output = Command::new("sh")
-        .arg("-c")
-        .arg(command)
-        .output()
+/// Runs a command with the terminal handed over (inherited stdio). The returned
+/// output is empty since nothing is captured.
+pub async fn execute_interactive(command: &str, cwd: Option<&Path>, wrapper: Option<&str>) -> Result<CommandOutput, String> {
+    let mut cmd = wrapped_command(command, wrapper)?;
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
+    }
+
+    let status = cmd
+        .status()
         .map_err(|e| format!("Failed to execute command: {}", e))?;
 
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    Ok(CommandOutput {
+        stdout: String::new(),
+        stderr: String::new(),
+        status,
+    })
+}
+
+/// Executables on PATH whose name starts with `prefix`, sorted and deduplicated.
+pub fn programs_with_prefix(prefix: &str) -> Vec<String> {
+    let Some(paths) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    let mut programs: Vec<String> = env::split_paths(&paths)
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            (name.starts_with(prefix) && is_executable(&entry.path())).then_some(name)
+        })
+        .collect();
+    programs.sort();
+    programs.dedup();
+    programs
+}
+
+/// Resolves `program` the way the shell would, returning its absolute path.
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    if program.contains('/') || program.contains('\\') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+
+    let extensions: Vec<String> = if cfg!(target_os = "windows") {
+        env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(|ext| ext.to_string())
+            .chain(std::iter::once(String::new()))
+            .collect()
+    } else {
+        vec![String::new()]
+    };
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths).find_map(|dir| {
+        extensions
+            .iter()
+            .map(|ext| dir.join(format!("{}{}", program, ext)))
+            .find(|candidate| is_executable(candidate))
+    })
+}
 
-    if !output.status.success() {
-        if !stderr.is_empty() {
-            return Err(format!("Error: {}", stderr));
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Whether tella runs with uid 0, asked once from `id -u`. Never on Windows.
+pub fn running_as_root() -> bool {
+    static ROOT: OnceLock<bool> = OnceLock::new();
+    *ROOT.get_or_init(|| {
+        if cfg!(target_os = "windows") {
+            return false;
         }
+        Command::new("id")
+            .arg("-u")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == "0")
+    })
+}
+
+/// Whether the systemd unit `service` is active. None without systemctl, or
+/// when systemd doesn't know the unit.
+pub fn service_running(service: &str) -> Option<bool> {
+    find_in_path("systemctl")?;
+    let status = Command::new("systemctl")
+        .args(["is-active", "--quiet", service])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .ok()?;
+    // is-active exits 3 for a known but stopped unit, 4 for an unknown one
+    match status.code() {
+        Some(0) => Some(true),
+        Some(3) => Some(false),
+        _ => None,
+    }
+}
+
+// Linux/BSD terminals tried in order when terminal_window is "auto" and $TERMINAL isn't set
+const TERMINALS: &[&str] = &[
+    "x-terminal-emulator",
+    "gnome-terminal",
+    "konsole",
+    "xfce4-terminal",
+    "kitty",
+    "alacritty",
+    "wezterm",
+    "xterm",
+];
+
+/// Opens `command` in a new terminal window and returns the terminal used.
+/// `terminal` is the terminal_window setting: "auto" detects one (Terminal or
+/// iTerm on macOS, Windows Terminal or a console window on Windows, $TERMINAL
+/// or the first of TERMINALS found elsewhere); anything else names the
+/// program. The shell starts in `cwd` when given and stays open afterwards so
+/// the output can be read.
+pub fn open_in_new_terminal(command: &str, terminal: &str, cwd: Option<&Path>) -> Result<String, String> {
+    let auto = terminal.eq_ignore_ascii_case("auto");
+    let (name, mut cmd) = if auto && cfg!(target_os = "macos") {
+        let app = if env::var("TERM_PROGRAM").is_ok_and(|p| p == "iTerm.app") { "iTerm" } else { "Terminal" };
+        // The new window's shell starts in the home directory, not ours
+        let command = match cwd {
+            Some(dir) => format!("cd '{}' && {}", dir.display().to_string().replace('\'', "'\\''"), command),
+            None => command.to_string(),
+        };
+        (app.to_string(), macos_terminal(app, &command))
+    } else if auto && cfg!(target_os = "windows") {
+        let (name, mut cmd) = match find_in_path("wt.exe") {
+            Some(_) => ("Windows Terminal", Command::new("wt.exe")),
+            None => {
+                let mut cmd = Command::new("cmd");
+                cmd.args(["/c", "start", ""]);
+                ("a console window", cmd)
+            }
+        };
+        add_shell_kept_open(&mut cmd, command);
+        (name.to_string(), cmd)
+    } else {
+        let program = if auto {
+            env::var("TERMINAL")
+                .ok()
+                .filter(|t| find_in_path(t).is_some())
+                .or_else(|| TERMINALS.iter().find(|t| find_in_path(t).is_some()).map(|t| t.to_string()))
+                .ok_or("No terminal emulator found. Set terminal_window to one in settings.json or export TERMINAL.")?
+        } else {
+            terminal.to_string()
+        };
+        let mut cmd = Command::new(&program);
+        cmd.args(terminal_exec_args(&program));
+        add_shell_kept_open(&mut cmd, command);
+        (program, cmd)
+    };
+
+    if let Some(dir) = cwd {
+        cmd.current_dir(dir);
     }
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", name, e))?;
+    Ok(name)
+}
+
+// Runs the command in the shell, which then stays open for the output to be read
+fn add_shell_kept_open(cmd: &mut Command, command: &str) {
+    let shell = shell_program();
+    match shell {
+        "powershell" => cmd.args([shell, "-NoExit", shell_flag(shell), command]),
+        _ => cmd.args([shell, shell_flag(shell), &format!("{}; exec {}", command, shell)]),
+    };
+}
+
+// How each terminal takes a program to run; -e is the xterm convention most follow
+fn terminal_exec_args(program: &str) -> &'static [&'static str] {
+    let name = Path::new(program).file_name().and_then(|n| n.to_str()).unwrap_or(program);
+    match name {
+        "gnome-terminal" | "kgx" => &["--"],
+        "xfce4-terminal" => &["-x"],
+        "wezterm" => &["start", "--"],
+        "kitty" => &[],
+        _ => &["-e"],
+    }
+}
+
+// Terminal.app and iTerm are scripted with AppleScript; both type the command
+// into a new window's interactive shell, which stays open afterwards
+fn macos_terminal(app: &str, command: &str) -> Command {
+    let quoted = format!("\"{}\"", command.replace('\\', "\\\\").replace('"', "\\\""));
+    let apple_script = match app {
+        "iTerm" => format!(
+            "tell application \"iTerm\" to tell current session of (create window with default profile) to write text {}",
+            quoted
+        ),
+        _ => format!("tell application \"Terminal\" to do script {}", quoted),
+    };
+    let mut cmd = Command::new("osascript");
+    cmd.args(["-e", &apple_script]);
+    cmd
+}
+
+/// Copies `text` with the platform clipboard tool (pbcopy, clip, wl-copy, xclip or xsel).
+pub fn copy_to_clipboard(text: &str) -> Result<(), String> {
+    let candidates: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(target_os = "windows") {
+        &[("clip", &[])]
+    } else {
+        &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"]), ("xsel", &["--clipboard", "--input"])]
+    };
+
+    let (program, args) = candidates
+        .iter()
+        .find(|(program, _)| find_in_path(program).is_some())
+        .ok_or("No clipboard tool found (install wl-copy, xclip or xsel)")?;
+
+    let mut child = Command::new(program)
+        .args(*args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Failed to write to {}: {}", program, e))?;
+    }
+
+    let status = child.wait().map_err(|e| format!("Failed to run {}: {}", program, e))?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", program, status));
+    }
+    Ok(())
+}
+
+/// Appends an executed command, its exit status and full output to `path`.
+pub fn append_log(path: &Path, command: &str, result: &Result<CommandOutput, String>) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open log file {}: {}", path.display(), e))?;
+
+    let (status, output) = match result {
+        Ok(output) => (
+            output
+                .status
+                .code()
+                .map_or("terminated by signal".to_string(), |code| code.to_string()),
+            output.combined(),
+        ),
+        Err(e) => ("failed to start".to_string(), e.clone()),
+    };
+
+    let entry = format!(
+        "=== {} ===\n$ {}\nexit status: {}\n{}\n\n",
+        clock::format_utc(clock::now_secs()),
+        command,
+        status,
+        output.trim_end()
+    );
 
-    Ok(if stderr.is_empty() { stdout } else { format!("{}\n{}", stdout, stderr) })
+    file.write_all(entry.as_bytes())
+        .map_err(|e| format!("Failed to write log file {}: {}", path.display(), e))
 }
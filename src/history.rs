@@ -0,0 +1,187 @@
+use crate::api::CommandSuggestion;
+use crate::cache;
+use crate::clock;
+use crate::settings::Settings;
+use regex::RegexBuilder;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: u64,
+    pub question: String,
+    pub command: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub severity: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl HistoryEntry {
+    /// True when the entry carries every tag in `tags` (case-insensitive).
+    pub fn has_tags(&self, tags: &[String]) -> bool {
+        tags.iter().all(|tag| self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    }
+}
+
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+// A serialized entry is rarely shorter than this, so a file below
+// max * MIN_LINE_BYTES can't be over the cap and isn't read at all
+const MIN_LINE_BYTES: u64 = 64;
+
+fn history_file() -> Result<PathBuf, String> {
+    Ok(Settings::get_settings_path()?.join("history.jsonl"))
+}
+
+/// Appends a suggestion to history.jsonl, unless history is disabled.
+pub fn record(question: &str, suggestion: &CommandSuggestion, tags: &[String], settings: Option<&Settings>) -> Result<(), String> {
+    if settings.is_some_and(|s| s.disable_history) {
+        return Ok(());
+    }
+
+    let entry = HistoryEntry {
+        timestamp: clock::now_secs(),
+        question: question.to_string(),
+        command: suggestion.command.clone(),
+        description: suggestion.description.clone(),
+        severity: suggestion.severity.clone(),
+        tags: tags.iter().map(|tag| tag.trim().to_lowercase()).filter(|tag| !tag.is_empty()).collect(),
+    };
+
+    let line = serde_json::to_string(&entry)
+        .map_err(|e| format!("Failed to serialize history entry: {}", e))?;
+
+    fs::create_dir_all(Settings::get_settings_path()?)
+        .map_err(|e| format!("Failed to create history directory: {}", e))?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_file()?)
+        .map_err(|e| format!("Failed to open history file: {}", e))?;
+
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write history file: {}", e))?;
+    drop(file);
+
+    let max_entries = settings.and_then(|s| s.history_max_entries).unwrap_or(DEFAULT_MAX_ENTRIES);
+    trim(max_entries)
+}
+
+// Drops the oldest entries once the file is 10% over the cap, so the rewrite
+// happens once every max/10 questions rather than on every run
+fn trim(max_entries: usize) -> Result<(), String> {
+    if max_entries == 0 {
+        return Ok(());
+    }
+
+    let path = history_file()?;
+    let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if size < max_entries as u64 * MIN_LINE_BYTES {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read history file: {}", e))?;
+    let lines: Vec<&str> = content.lines().collect();
+    if lines.len() <= max_entries + max_entries / 10 {
+        return Ok(());
+    }
+
+    let mut kept = lines[lines.len() - max_entries..].join("\n");
+    kept.push('\n');
+
+    replace_file(&path, &kept).map_err(|e| format!("Failed to trim history file: {}", e))
+}
+
+/// `tella --prune-history --keep N`: keeps the newest `keep` entries and
+/// rewrites the file without blank or unreadable lines. Returns how many
+/// entries were removed.
+pub fn prune(keep: usize) -> Result<usize, String> {
+    let path = history_file()?;
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let entries = load()?;
+    let removed = entries.len().saturating_sub(keep);
+
+    let mut kept = String::new();
+    for entry in &entries[removed..] {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| format!("Failed to serialize history entry: {}", e))?;
+        kept.push_str(&line);
+        kept.push('\n');
+    }
+
+    replace_file(&path, &kept).map_err(|e| format!("Failed to prune history file: {}", e))?;
+    Ok(removed)
+}
+
+// Writes a temp file next to `path`, flushes it to disk and renames it over
+// `path`, so an interruption leaves either the old file or the new one
+fn replace_file(path: &Path, content: &str) -> std::io::Result<()> {
+    let temp_file = path.with_extension(format!("jsonl.{}.tmp", std::process::id()));
+    let result = fs::File::create(&temp_file)
+        .and_then(|mut file| {
+            file.write_all(content.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_file, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_file);
+    }
+    result
+}
+
+/// Reads all entries, oldest first. Lines that fail to parse are skipped.
+pub fn load() -> Result<Vec<HistoryEntry>, String> {
+    let path = history_file()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read history file: {}", e))?;
+
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+pub fn clear() -> Result<(), String> {
+    let path = history_file()?;
+    if path.exists() {
+        fs::remove_file(&path).map_err(|e| format!("Failed to clear history: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Case-insensitive match against question or command. `term` is a regex;
+/// if it doesn't compile it's treated as a plain substring.
+pub fn search(term: &str) -> Result<Vec<HistoryEntry>, String> {
+    let pattern = RegexBuilder::new(term)
+        .case_insensitive(true)
+        .build()
+        .or_else(|_| RegexBuilder::new(&regex::escape(term)).case_insensitive(true).build())
+        .map_err(|e| format!("Invalid search term: {}", e))?;
+
+    Ok(load()?
+        .into_iter()
+        .filter(|entry| pattern.is_match(&entry.question) || pattern.is_match(&entry.command))
+        .collect())
+}
+
+/// Most recent entry asked with the same question (compared normalized).
+pub fn find_question(question: &str) -> Result<Option<HistoryEntry>, String> {
+    let wanted = cache::normalize_question(question);
+    Ok(load()?
+        .into_iter()
+        .rev()
+        .find(|entry| cache::normalize_question(&entry.question) == wanted))
+}
@@ -0,0 +1,67 @@
+use crate::clock;
+use crate::command_executor::CommandOutput;
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+// Keeps the state file (and the later prompt) small; the end of the output matters most
+const MAX_STORED_OUTPUT: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LastRun {
+    pub timestamp: u64,
+    pub command: String,
+    pub exit_code: Option<i32>,
+    pub output: String,
+}
+
+fn last_run_file() -> Result<PathBuf, String> {
+    Ok(Settings::get_settings_path()?.join("last_run.json"))
+}
+
+/// Stores the command and its output for `tella --explain-last-output`,
+/// unless history is disabled.
+pub fn record(command: &str, output: &CommandOutput, settings: Option<&Settings>) -> Result<(), String> {
+    if settings.is_some_and(|s| s.disable_history) {
+        return Ok(());
+    }
+
+    let last_run = LastRun {
+        timestamp: clock::now_secs(),
+        command: command.to_string(),
+        exit_code: output.status.code(),
+        output: tail(&output.combined(), MAX_STORED_OUTPUT).to_string(),
+    };
+
+    let content = serde_json::to_string(&last_run)
+        .map_err(|e| format!("Failed to serialize last run: {}", e))?;
+
+    fs::create_dir_all(Settings::get_settings_path()?)
+        .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+
+    fs::write(last_run_file()?, content).map_err(|e| format!("Failed to write last run file: {}", e))
+}
+
+pub fn load() -> Result<Option<LastRun>, String> {
+    let path = last_run_file()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read last run file: {}", e))?;
+
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse last run file: {}", e))
+}
+
+// Last `max_bytes` of `text`, cut on a char boundary
+fn tail(text: &str, max_bytes: usize) -> &str {
+    let mut start = text.len().saturating_sub(max_bytes);
+    while !text.is_char_boundary(start) {
+        start += 1;
+    }
+    &text[start..]
+}
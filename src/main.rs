@@ -1,13 +1,24 @@
 mod cli;
 mod api;
+mod cache;
+mod clock;
+mod history;
+mod last_run;
 mod ui;
 mod command_executor;
 mod settings;
 mod updater;
+mod safety;
+mod quiz;
+mod postprocess;
+mod utf8;
 
 use clap::Parser;
 use colored::*;
-use std::io;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+const HISTORY_LIST_LIMIT: usize = 20;
 
 #[derive(Parser, Debug)]
 #[command(name = "tella")]
@@ -19,6 +30,174 @@ struct Args {
     #[arg(long, action)]
     upgrade: bool,
 
+    /// Check for a new version now, even if the update banner is snoozed
+    #[arg(long, action)]
+    check_update: bool,
+
+    /// Explain the given command instead of generating one
+    #[arg(long, action)]
+    explain: bool,
+
+    /// Explain the severity colors and what tella does for each
+    #[arg(long, action)]
+    legend: bool,
+
+    /// Show recent questions and suggested commands
+    #[arg(long, action)]
+    history: bool,
+
+    /// Delete the saved history
+    #[arg(long, action)]
+    history_clear: bool,
+
+    /// Remove all but the newest history entries (use with --keep)
+    #[arg(long, action, requires = "keep")]
+    prune_history: bool,
+
+    /// How many history entries --prune-history keeps
+    #[arg(long, value_name = "N", requires = "prune_history")]
+    keep: Option<usize>,
+
+    /// Practice recalling the flags of commands from history (spaced repetition)
+    #[arg(long, action)]
+    quiz: bool,
+
+    /// Search history questions and commands (regex or plain text)
+    #[arg(long, value_name = "TERM")]
+    history_search: Option<String>,
+
+    /// Print only the suggested command, without the interactive menu
+    #[arg(long, short, action)]
+    quiet: bool,
+
+    /// Print the full suggestion as JSON
+    #[arg(long, action)]
+    json: bool,
+
+    /// Print the suggestion in a non-interactive format
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    format: Option<cli::OutputFormat>,
+
+    /// Omit the trailing newline in --quiet/--json output
+    #[arg(long, action)]
+    no_trailing_newline: bool,
+
+    /// Directory to run the suggested command in
+    #[arg(long, value_name = "DIR")]
+    cwd: Option<PathBuf>,
+
+    /// Append each executed command and its output to FILE
+    #[arg(long, value_name = "FILE")]
+    log_output: Option<PathBuf>,
+
+    /// Run the suggestion without the menu (dangerous commands still ask)
+    #[arg(long, short, action)]
+    yes: bool,
+
+    /// Don't ask before running dangerous commands with --yes (this run only)
+    #[arg(long, action)]
+    no_severity_gate: bool,
+
+    /// Ask the model to interpret the output of the last command tella ran
+    #[arg(long, action)]
+    explain_last_output: bool,
+
+    /// Check that the configured providers are reachable
+    #[arg(long, action)]
+    provider_status: bool,
+
+    /// How explanations are structured: prose (default), bullets or steps
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    explain_format: Option<api::ExplainFormat>,
+
+    /// Detail level the Explain step starts at: short, medium (default) or long
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    explain_verbosity: Option<api::ExplainVerbosity>,
+
+    /// Show details about the configured Ollama model
+    #[arg(long, action)]
+    model_info: bool,
+
+    /// Attach a file (e.g. a log) as context for the question
+    #[arg(long, value_name = "FILE")]
+    context: Option<PathBuf>,
+
+    /// Never touch the network; answer from the cache or history only
+    #[arg(long, action)]
+    offline: bool,
+
+    /// Pick a recent command from history to run or copy (no API call)
+    #[arg(long, action)]
+    pick: bool,
+
+    /// Tag the saved history entry; with --history/--pick, only show entries with this tag
+    #[arg(long, value_name = "TAG", value_delimiter = ',')]
+    tag: Vec<String>,
+
+    /// When a command fails, explain the error and suggest a fix automatically
+    #[arg(long, action)]
+    explain_errors: bool,
+
+    /// Ask two models (e.g. llama3.2 cerebras/llama3.3-70b) and compare their answers side by side
+    #[arg(long, num_args = 2, value_names = ["MODEL_A", "MODEL_B"])]
+    compare: Option<Vec<String>>,
+
+    /// Print the provider's unparsed reply for the question and exit (for debugging)
+    #[arg(long, action)]
+    raw_response: bool,
+
+    /// Ask another question in the same run (repeatable); `tella q1 -- q2` works too
+    #[arg(long, value_name = "QUESTION")]
+    ask: Vec<String>,
+
+    /// Page long command output and explanations through $PAGER (or less -R)
+    #[arg(long, action)]
+    pager: bool,
+
+    /// Shell to suggest and run commands for (bash, zsh, sh, ksh, fish, powershell); defaults to the OS's
+    #[arg(long, value_name = "SHELL")]
+    shell: Option<String>,
+
+    /// Compare your own command with tella's suggestion for the question
+    #[arg(long, value_name = "MY_COMMAND")]
+    diff: Option<String>,
+
+    /// Keep asking in a prompt loop; act on earlier suggestions with "run N" / "explain N"
+    #[arg(long, action)]
+    session: bool,
+
+    /// Pipe the suggested command's output into PROGRAM when it runs, e.g. "grep foo"
+    #[arg(long, value_name = "PROGRAM")]
+    exec_pipe: Option<String>,
+
+    /// Check settings.json and report every problem found
+    #[arg(long, action)]
+    validate_config: bool,
+
+    /// Seconds between runs for the Watch action (default 2)
+    #[arg(long, value_name = "SECS")]
+    interval: Option<u64>,
+
+    /// Ask every model in consensus_models and flag it when their commands differ
+    #[arg(long, action)]
+    consensus: bool,
+
+    /// Print only the model's safe/warning/dangerous rating of the given command
+    #[arg(long, action)]
+    severity_only: bool,
+
+    /// Directory for settings, history and the cache (e.g. when the default is read-only)
+    #[arg(long, value_name = "DIR")]
+    config_path: Option<PathBuf>,
+
+    /// Ask for N alternative commands
+    #[arg(long, value_name = "N")]
+    alternatives: Option<usize>,
+
+    /// Pick the first alternative automatically (with --yes/--quiet/--json)
+    #[arg(long, action)]
+    first: bool,
+
     #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
     question: Vec<String>,
 }
@@ -29,12 +208,41 @@ async fn main() -> io::Result<()> {
 
     let args = Args::parse();
 
+    if args.offline {
+        api::set_offline();
+    }
+
+    if let Some(dir) = &args.config_path {
+        settings::set_config_path(dir.clone());
+    }
+
+    if let Some(shell) = &args.shell {
+        if let Err(e) = command_executor::set_shell(shell) {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    }
+
+    if let Some(warning) = clock::skew_warning() {
+        eprintln!("{}", format!("⚠️  {}", warning).yellow());
+    }
+
     if args.upgrade {
         match updater::perform_upgrade().await {
             Ok(_) => return Ok(()),
             Err(e) => {
                 eprintln!("{}", format!("❌ Error: {}", e).red());
-                return Err(io::Error::new(io::ErrorKind::Other, e));
+                return Err(io::Error::other(e));
+            }
+        }
+    }
+
+    if args.check_update {
+        match updater::force_update_check().await {
+            Ok(_) => return Ok(()),
+            Err(e) => {
+                eprintln!("{}", format!("❌ Error: {}", e).red());
+                return Err(io::Error::other(e));
             }
         }
     }
@@ -44,18 +252,197 @@ async fn main() -> io::Result<()> {
             Ok(_) => return Ok(()),
             Err(e) => {
                 eprintln!("{}", format!("❌ Error: {}", e).red());
-                return Err(io::Error::new(io::ErrorKind::Other, e));
+                return Err(io::Error::other(e));
+            }
+        }
+    }
+
+    if args.validate_config {
+        return cli::handle_validate_config();
+    }
+
+    if args.legend {
+        cli::print_severity_legend();
+        return Ok(());
+    }
+
+    if args.history_clear {
+        match history::clear() {
+            Ok(_) => {
+                println!("{}", "✅ History cleared".green());
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", format!("❌ Error: {}", e).red());
+                return Err(io::Error::other(e));
+            }
+        }
+    }
+
+    // clap only accepts --prune-history and --keep together
+    if let Some(keep) = args.keep {
+        match history::prune(keep) {
+            Ok(removed) => {
+                println!("{}", format!("✅ Removed {} history entries, kept the newest {}", removed, keep).green());
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", format!("❌ Error: {}", e).red());
+                return Err(io::Error::other(e));
+            }
+        }
+    }
+
+    if args.history || args.history_search.is_some() {
+        let entries = match &args.history_search {
+            Some(term) => history::search(term),
+            None => history::load(),
+        }
+        .map(|entries| {
+            let entries: Vec<_> = entries.into_iter().filter(|entry| entry.has_tags(&args.tag)).collect();
+            if args.history_search.is_some() {
+                return entries;
+            }
+            let skip = entries.len().saturating_sub(HISTORY_LIST_LIMIT);
+            entries.into_iter().skip(skip).collect()
+        });
+        match entries {
+            Ok(entries) => {
+                cli::print_history(&entries);
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("{}", format!("❌ Error: {}", e).red());
+                return Err(io::Error::other(e));
             }
         }
     }
 
-    tokio::spawn(async {
-        updater::check_for_updates().await;
-    });
+    if args.quiz {
+        return quiz::handle_quiz();
+    }
+
+    let scripted = args.quiet || args.json || args.format.is_some() || args.raw_response || args.diff.is_some() || args.severity_only;
+    if !scripted && !settings::Settings::is_configured() && io::stdin().is_terminal() {
+        if let Err(e) = offer_first_run_setup().await {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    }
+
+    if args.pick {
+        let options = cli::AskOptions {
+            log_output: args.log_output.clone(),
+            tags: args.tag.clone(),
+            pager: args.pager,
+            ..Default::default()
+        };
+        return cli::handle_pick(&options).await;
+    }
+
+    if args.model_info {
+        return cli::print_model_info().await;
+    }
+
+    if args.provider_status {
+        return cli::print_provider_status().await;
+    }
+
+    if args.explain_last_output {
+        let options = cli::AskOptions {
+            pager: args.pager,
+            ..Default::default()
+        };
+        return cli::handle_explain_last_output(&options).await;
+    }
+
+    if !args.offline {
+        tokio::spawn(async {
+            updater::check_for_updates().await;
+        });
+    }
+
+    // `tella q1 -- q2` and each --ask are separate questions
+    let questions: Vec<String> = args
+        .question
+        .split(|word| word == "--")
+        .map(|words| words.join(" "))
+        .chain(args.ask.iter().cloned())
+        .filter(|question| !question.trim().is_empty())
+        .collect();
+
+    if !questions.is_empty() || args.session {
+        // Fire-and-forget: never delays the actual request
+        if let Ok(settings) = settings::Settings::load() {
+            if settings.prewarm && settings.provider == "ollama" && !args.offline {
+                tokio::spawn(async move {
+                    let _ = api::warm_up_ollama(&settings).await;
+                });
+            }
+        }
+
+        let question = questions.join(" ");
+
+        let cwd = match &args.cwd {
+            Some(dir) if dir.is_dir() => Some(dir.canonicalize()?),
+            Some(dir) => {
+                let message = format!("--cwd directory does not exist: {}", dir.display());
+                eprintln!("{}", format!("❌ Error: {}", message).red());
+                return Err(io::Error::other(message));
+            }
+            None => None,
+        };
 
-    if !args.question.is_empty() {
-        let question = args.question.join(" ");
-        cli::handle_ask_command(&question).await?;
+        let attachment = match &args.context {
+            Some(path) => match api::Attachment::from_file(path) {
+                Ok(attachment) => Some(attachment),
+                Err(e) => {
+                    eprintln!("{}", format!("❌ Error: {}", e).red());
+                    return Err(io::Error::other(e));
+                }
+            },
+            None => None,
+        };
+
+        let options = cli::AskOptions {
+            quiet: args.quiet,
+            json: args.json,
+            no_trailing_newline: args.no_trailing_newline,
+            cwd,
+            log_output: args.log_output,
+            yes: args.yes,
+            first: args.first,
+            alternatives: args.alternatives,
+            format: args.format,
+            no_severity_gate: args.no_severity_gate,
+            explain_format: args.explain_format,
+            attachment,
+            tags: args.tag,
+            explain_errors: args.explain_errors,
+            pager: args.pager,
+            explain_verbosity: args.explain_verbosity,
+            interval: args.interval,
+            exec_pipe: args.exec_pipe,
+        };
+        if args.session {
+            cli::handle_session(&options).await?;
+        } else if let Some(my_command) = &args.diff {
+            cli::handle_diff(my_command, &question, &options).await?;
+        } else if args.raw_response {
+            cli::handle_raw_response(&question, &options).await?;
+        } else if let Some(models) = &args.compare {
+            cli::handle_compare(&question, models, &options).await?;
+        } else if args.consensus {
+            cli::handle_consensus(&question, &options).await?;
+        } else if args.severity_only {
+            cli::handle_severity_only(&question, &options).await?;
+        } else if args.explain {
+            cli::handle_explain_command(&question, &options).await?;
+        } else if questions.len() > 1 {
+            cli::handle_ask_commands(&questions, &options).await?;
+        } else {
+            cli::handle_ask_command(&question, &options).await?;
+        }
     } else {
         println!("{}", "tella - Command Assistant v0.1.21".bold().cyan());
         println!("{}", "━".repeat(50));
@@ -64,6 +451,26 @@ async fn main() -> io::Result<()> {
         println!("  {} tella show me the last 5 git commits", "$".cyan());
         println!("  {} tella --settings", "$".cyan());
         println!("  {} tella --upgrade", "$".cyan());
+        println!("  {} tella --explain \"tar -xzvf archive.tar.gz\"", "$".cyan());
+        println!("  {} tella --severity-only \"rm -rf build\"", "$".cyan());
+        println!("  {} tella --legend", "$".cyan());
+        println!("  {} tella --validate-config", "$".cyan());
+        println!("  {} tella --explain-last-output", "$".cyan());
+        println!("  {} tella --provider-status", "$".cyan());
+        println!("  {} tella --model-info", "$".cyan());
+        println!("  {} tella --history-search git", "$".cyan());
+        println!("  {} tella --pick", "$".cyan());
+        println!("  {} tella --quiz", "$".cyan());
+        println!("  {} tella --session", "$".cyan());
+        println!("  {} tella --quiet --no-trailing-newline list files", "$".cyan());
+        println!("  {} tella --cwd /tmp list files", "$".cyan());
+        println!("  {} tella --exec-pipe \"sort -h\" show folder sizes", "$".cyan());
+        println!("  {} tella --context error.log what command fixes this", "$".cyan());
+        println!("  {} tella --alternatives 3 --first --yes list files", "$".cyan());
+        println!("  {} tella --ask \"list files\" --ask \"show disk usage\"", "$".cyan());
+        println!("  {} tella --diff \"ls -l | grep txt\" list text files", "$".cyan());
+        println!("  {} tella --compare llama3.2 cerebras/llama3.3-70b list files", "$".cyan());
+        println!("  {} tella --consensus delete old docker volumes", "$".cyan());
         println!("\n{}", "Examples:".bold());
         println!("  {} tella how to list files in directory", "$".cyan());
         println!("  {} tella find large files on my system", "$".cyan());
@@ -72,3 +479,22 @@ async fn main() -> io::Result<()> {
 
     Ok(())
 }
+
+// First run: offer to configure now instead of failing with "Settings file not found"
+async fn offer_first_run_setup() -> Result<(), String> {
+    println!("{}", "👋 Welcome to tella! No settings found yet.".bold().cyan());
+    print!("{} ", "Run the setup now? (Y/n):".bold());
+    io::stdout().flush().map_err(|e| e.to_string())?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).map_err(|e| e.to_string())?;
+    let input = input.trim();
+    if !input.is_empty() && !input.eq_ignore_ascii_case("y") && !input.eq_ignore_ascii_case("yes") {
+        println!("{}", "You can configure tella later with 'tella --settings'.".dimmed());
+        return Ok(());
+    }
+
+    settings::Settings::interactive_setup().await?;
+    println!();
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::command_executor;
+use crate::settings::Settings;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// A rewrite of a suggested command: the new command, or None to leave it.
+type Step = fn(&str, &Settings) -> Option<String>;
+
+// Applied in order; each step checks its own setting
+const STEPS: &[Step] = &[normalize_path_separators];
+
+/// Runs the post-processing steps over a suggested command before it is shown,
+/// cached or run.
+pub fn postprocess_command(command: &str, settings: &Settings) -> String {
+    STEPS
+        .iter()
+        .fold(command.to_string(), |command, step| step(&command, settings).unwrap_or(command))
+}
+
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\S+").expect("valid word pattern"));
+
+// `./src/app.js`, `../x`, `~/bin`: only paths that can't be a URL, a git ref or a cmd-style /flag
+static UNIX_RELATIVE_PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^(\.{1,2}|~)(/[^/\s:*?"<>|]+)+/?$"#).expect("valid path pattern"));
+
+// `C:/Users/me`
+static DRIVE_PATH_WITH_SLASHES: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^[A-Za-z]:(/[^/\s:*?"<>|]+)+/?$"#).expect("valid path pattern"));
+
+// `.\scripts\build.sh`, `~\notes`
+static WINDOWS_RELATIVE_PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\.{1,2}|~)(\\[\w.@+-]+)+\\?$").expect("valid path pattern"));
+
+// `src\main.rs`; a backslash can also be an escape (`a\nb`), so these need more
+// evidence. A name never starts right after the backslash with `.` (`192\.168`).
+static WINDOWS_BARE_PATH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[\w.@+-]+(\\[\w@+-][\w.@+-]*)+\\?$").expect("valid path pattern"));
+
+// With `normalize_paths`, turns the separators of paths written for the other
+// platform into the execution shell's: `/` for PowerShell becomes `\`, `\` for
+// the POSIX shells becomes `/`. Only words that are clearly local paths are
+// touched, so URLs, flags like `/s`, refs like `origin/main`, regexes and
+// escapes stay as they are. Drive paths (`C:\...`) are left alone on Unix,
+// where they have no equivalent.
+fn normalize_path_separators(command: &str, settings: &Settings) -> Option<String> {
+    if !settings.normalize_paths {
+        return None;
+    }
+    let to_windows = command_executor::shell_program() == "powershell";
+
+    let mut result = String::with_capacity(command.len());
+    let mut copied = 0;
+    for word in WORD.find_iter(command) {
+        if let Some(rewritten) = rewrite_word(word.as_str(), to_windows) {
+            result.push_str(&command[copied..word.start()]);
+            result.push_str(&rewritten);
+            copied = word.end();
+        }
+    }
+    if copied == 0 {
+        return None;
+    }
+    result.push_str(&command[copied..]);
+    Some(result)
+}
+
+// A plain word, one wrapped in quotes, or the value of `--flag=value`
+fn rewrite_word(word: &str, to_windows: bool) -> Option<String> {
+    if word.starts_with('-') {
+        let (flag, value) = word.split_once('=')?;
+        return rewrite_word(value, to_windows).map(|value| format!("{}={}", flag, value));
+    }
+
+    for quote in ['"', '\''] {
+        if let Some(inner) = word.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)) {
+            return rewrite_path(inner, to_windows, true).map(|path| format!("{}{}{}", quote, path, quote));
+        }
+    }
+    if word.contains(['"', '\'']) {
+        return None;
+    }
+    rewrite_path(word, to_windows, false)
+}
+
+fn rewrite_path(path: &str, to_windows: bool, quoted: bool) -> Option<String> {
+    if to_windows {
+        let is_path = UNIX_RELATIVE_PATH.is_match(path) || DRIVE_PATH_WITH_SLASHES.is_match(path);
+        return is_path.then(|| path.replace('/', "\\"));
+    }
+
+    // Quoted strings are where backslash escapes live (printf "a\nb"), so bare
+    // paths are only taken unquoted, and with two separators or a file extension
+    let bare_path = || {
+        let last = path.trim_end_matches('\\').rsplit('\\').next().unwrap_or_default();
+        !quoted && WINDOWS_BARE_PATH.is_match(path) && (path.matches('\\').count() >= 2 || last.rfind('.').is_some_and(|dot| dot > 0))
+    };
+    let is_path = WINDOWS_RELATIVE_PATH.is_match(path) || bare_path();
+    is_path.then(|| path.replace('\\', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_paths_become_unix_paths() {
+        assert_eq!(rewrite_word(r".\scripts\build.sh", false).as_deref(), Some("./scripts/build.sh"));
+        assert_eq!(rewrite_word(r"src\main.rs", false).as_deref(), Some("src/main.rs"));
+        assert_eq!(rewrite_word(r"src\lib\mod", false).as_deref(), Some("src/lib/mod"));
+        assert_eq!(rewrite_word(r"--config=.\conf\app.toml", false).as_deref(), Some("--config=./conf/app.toml"));
+    }
+
+    #[test]
+    fn unix_paths_become_windows_paths() {
+        assert_eq!(rewrite_word("./src/app.js", true).as_deref(), Some(r".\src\app.js"));
+        assert_eq!(rewrite_word("\"C:/Users/me\"", true).as_deref(), Some(r#""C:\Users\me""#));
+    }
+
+    #[test]
+    fn urls_flags_and_refs_are_left_alone() {
+        for word in ["https://example.com/a/b", "/s", "/?", "origin/main", "src/main.rs"] {
+            assert_eq!(rewrite_word(word, true), None, "{}", word);
+        }
+    }
+
+    #[test]
+    fn regex_escapes_are_left_alone() {
+        for word in [r"192\.168\.1\.1", r"\d+\.\d+", r"a\nb", r"foo\*\*", r#""src\main.rs""#] {
+            assert_eq!(rewrite_word(word, false), None, "{}", word);
+        }
+    }
+}
@@ -0,0 +1,302 @@
+use crate::clock;
+use crate::history;
+use crate::settings::Settings;
+use colored::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+// Leitner-style boxes: a right answer moves a card to the next interval,
+// a wrong one sends it back to the first
+const REVIEW_INTERVALS_DAYS: [u64; 5] = [1, 3, 7, 16, 35];
+
+const SESSION_SIZE: usize = 10;
+
+// Only recent history feeds the deck, so an old 10k-entry file doesn't
+// turn into a 10k-card backlog on the first quiz
+const HISTORY_WINDOW: usize = 50;
+
+const BLANK: &str = "____";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Deck {
+    #[serde(default)]
+    cards: Vec<Card>,
+    #[serde(default)]
+    last_reminder: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Card {
+    command: String,
+    #[serde(default)]
+    question: String,
+    #[serde(default)]
+    level: usize,
+    #[serde(default)]
+    due: u64,
+    #[serde(default)]
+    reviews: u32,
+}
+
+impl Card {
+    fn new(command: &str, question: &str) -> Self {
+        Card {
+            command: command.to_string(),
+            question: question.to_string(),
+            level: 0,
+            due: clock::now_secs(),
+            reviews: 0,
+        }
+    }
+
+    // The command with one flag blanked out, and that flag. Each review hides
+    // the next flag, so a command with several gets all of them asked in turn.
+    fn blanked(&self) -> Option<(String, String)> {
+        let flags = flag_spans(&self.command);
+        let (start, end) = *flags.get(self.reviews as usize % flags.len().max(1))?;
+        let answer = self.command[start..end].to_string();
+        Some((format!("{}{}{}", &self.command[..start], BLANK, &self.command[end..]), answer))
+    }
+
+    fn schedule(&mut self, correct: bool) {
+        let now = clock::now_secs();
+        self.reviews += 1;
+        if correct {
+            self.due = now + REVIEW_INTERVALS_DAYS[self.level] * DAY_SECS;
+            self.level = (self.level + 1).min(REVIEW_INTERVALS_DAYS.len() - 1);
+        } else {
+            self.level = 0;
+            self.due = now;
+        }
+    }
+}
+
+fn quiz_file() -> Result<PathBuf, String> {
+    Ok(Settings::get_settings_path()?.join("quiz.json"))
+}
+
+fn load_deck() -> Result<Deck, String> {
+    let path = quiz_file()?;
+    if !path.exists() {
+        return Ok(Deck::default());
+    }
+
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read quiz file: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse quiz file: {}", e))
+}
+
+fn save_deck(deck: &Deck) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(deck)
+        .map_err(|e| format!("Failed to serialize quiz file: {}", e))?;
+
+    fs::create_dir_all(Settings::get_settings_path()?)
+        .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+
+    fs::write(quiz_file()?, content).map_err(|e| format!("Failed to write quiz file: {}", e))
+}
+
+// Byte ranges of the options worth asking about: `-la`, `--depth` (without
+// its `=value`). Lone `-`/`--` and negative numbers like `-5` aren't flags.
+fn flag_spans(command: &str) -> Vec<(usize, usize)> {
+    static WORD: OnceLock<Regex> = OnceLock::new();
+    let word = WORD.get_or_init(|| Regex::new(r"\S+").unwrap());
+
+    word.find_iter(command)
+        .filter_map(|m| {
+            let token = m.as_str();
+            let name = token.split('=').next().unwrap_or(token);
+            let body = name.trim_start_matches('-');
+            let is_flag = token.starts_with('-')
+                && name.len() - body.len() <= 2
+                && body.chars().next().is_some_and(|c| c.is_ascii_alphabetic());
+            is_flag.then(|| (m.start(), m.start() + name.len()))
+        })
+        .collect()
+}
+
+/// Whether the command has a flag to quiz on.
+pub fn is_quizzable(command: &str) -> bool {
+    !flag_spans(command).is_empty()
+}
+
+// Combined short flags count in any order, so `-al` is as good as `-la`
+fn answer_matches(expected: &str, given: &str) -> bool {
+    if given == expected {
+        return true;
+    }
+    let short = |flag: &str| flag.starts_with('-') && !flag.starts_with("--");
+    if !short(expected) || !short(given) || expected.len() != given.len() {
+        return false;
+    }
+    let mut expected: Vec<char> = expected.chars().collect();
+    let mut given: Vec<char> = given.chars().collect();
+    expected.sort_unstable();
+    given.sort_unstable();
+    expected == given
+}
+
+// Adds the newest distinct history commands that aren't in the deck yet
+fn sync_from_history(deck: &mut Deck) -> Result<(), String> {
+    let entries = history::load()?;
+    let skip = entries.len().saturating_sub(HISTORY_WINDOW);
+    for entry in entries.into_iter().skip(skip) {
+        if is_quizzable(&entry.command) && !deck.cards.iter().any(|card| card.command == entry.command) {
+            deck.cards.push(Card::new(&entry.command, &entry.question));
+        }
+    }
+    Ok(())
+}
+
+// Shows the blanked command and reads the answer. None means the user quit.
+fn ask_card(card: &Card) -> io::Result<Option<bool>> {
+    let Some((blanked, answer)) = card.blanked() else {
+        return Ok(Some(true));
+    };
+
+    if !card.question.is_empty() {
+        println!("{} {}", "❓".bold(), card.question.bold());
+    }
+    println!("   {}", blanked.yellow());
+    print!("{} ", "Missing flag (Enter to reveal, q to stop):".dimmed());
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        println!();
+        return Ok(None);
+    }
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("q") {
+        return Ok(None);
+    }
+
+    let correct = answer_matches(&answer, input);
+    if correct {
+        println!("{}", "✅ Correct!".green());
+    } else if input.is_empty() {
+        println!("{}", format!("💡 It's {}", answer).cyan());
+    } else {
+        println!("{}", format!("❌ Not quite, it's {}", answer).red());
+    }
+    println!("   {}", card.command.bold().yellow());
+    Ok(Some(correct))
+}
+
+fn next_review_note(card: &Card) -> String {
+    let days = card.due.saturating_sub(clock::now_secs()).div_ceil(DAY_SECS);
+    match days {
+        0 => "Asked again next time.".to_string(),
+        1 => "Next review in 1 day.".to_string(),
+        _ => format!("Next review in {} days.", days),
+    }
+}
+
+/// `tella --quiz`: asks the due cards, oldest first, after pulling new
+/// commands in from recent history.
+pub fn handle_quiz() -> io::Result<()> {
+    let mut deck = match load_deck().and_then(|mut deck| sync_from_history(&mut deck).map(|()| deck)) {
+        Ok(deck) => deck,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    let now = clock::now_secs();
+    let mut due: Vec<usize> = (0..deck.cards.len()).filter(|&i| deck.cards[i].due <= now).collect();
+    due.sort_by_key(|&i| deck.cards[i].due);
+    due.truncate(SESSION_SIZE);
+
+    if due.is_empty() {
+        if deck.cards.is_empty() {
+            println!("{}", "Nothing to quiz on yet. Ask tella for a few commands with flags first.".yellow());
+        } else {
+            println!("{}", "🎉 No commands are due for review. Come back later!".green());
+        }
+        return save(&deck);
+    }
+
+    println!("{}", format!("🧠 {} command(s) to review. Fill in the missing flag.", due.len()).bold().cyan());
+    let mut asked = 0;
+    let mut correct = 0;
+    for i in due {
+        println!();
+        let Some(right) = ask_card(&deck.cards[i])? else {
+            break;
+        };
+        deck.cards[i].schedule(right);
+        println!("{}", next_review_note(&deck.cards[i]).dimmed());
+        save(&deck)?;
+        asked += 1;
+        correct += right as usize;
+    }
+
+    println!();
+    println!("{}", format!("Reviewed {}, {} correct.", asked, correct).bold());
+    Ok(())
+}
+
+/// The "Quiz me" action: adds the command to the deck if needed and asks it
+/// right away.
+pub fn quiz_command(command: &str, question: &str) -> io::Result<()> {
+    let mut deck = match load_deck() {
+        Ok(deck) => deck,
+        Err(e) => {
+            eprintln!("{}", format!("❌ Error: {}", e).red());
+            return Err(io::Error::other(e));
+        }
+    };
+
+    let index = match deck.cards.iter().position(|card| card.command == command) {
+        Some(index) => index,
+        None => {
+            deck.cards.push(Card::new(command, question));
+            deck.cards.len() - 1
+        }
+    };
+
+    println!();
+    if let Some(right) = ask_card(&deck.cards[index])? {
+        deck.cards[index].schedule(right);
+        println!("{}", next_review_note(&deck.cards[index]).dimmed());
+        save(&deck)?;
+    }
+    Ok(())
+}
+
+/// With `learn_mode` on, a nudge when cards are due, at most once a day.
+pub fn due_reminder(settings: Option<&Settings>) -> Option<String> {
+    if !settings.is_some_and(|s| s.learn_mode) {
+        return None;
+    }
+
+    let mut deck = load_deck().ok()?;
+    if clock::age_secs(deck.last_reminder) < DAY_SECS {
+        return None;
+    }
+    sync_from_history(&mut deck).ok()?;
+
+    let now = clock::now_secs();
+    let due = deck.cards.iter().filter(|card| card.due <= now).count();
+    if due == 0 {
+        return None;
+    }
+
+    deck.last_reminder = now;
+    save_deck(&deck).ok()?;
+    Some(format!("📚 {} command(s) due for review. Run 'tella --quiz' to practice.", due))
+}
+
+fn save(deck: &Deck) -> io::Result<()> {
+    save_deck(deck).map_err(|e| {
+        eprintln!("{}", format!("❌ Error: {}", e).red());
+        io::Error::other(e)
+    })
+}
@@ -0,0 +1,226 @@
+use crate::clock;
+use crate::command_executor;
+use crate::settings::Settings;
+use colored::*;
+use regex::Regex;
+use std::path::PathBuf;
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Commands that can wreck a machine in one go. These always ask for
+/// confirmation, whatever the model's severity or the --yes/--no-severity-gate flags.
+static CATASTROPHIC_PATTERNS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        (r"\brm\s+(-[a-zA-Z]*\s+)*-[a-zA-Z]*[rR][a-zA-Z]*\s+(-[a-zA-Z]*\s+)*(/|/\*|~|~/|\$HOME)(\s|$)", "recursively deletes the root or home directory"),
+        (r"--no-preserve-root", "disables rm's root directory protection"),
+        (r"\bmkfs(\.\w+)?\b", "formats a filesystem"),
+        (r"\bdd\b.*\bof=/dev/(sd|nvme|hd|vd|xvd|mmcblk|disk)", "writes directly to a block device"),
+        (r">\s*/dev/(sd|nvme|hd|vd|xvd|mmcblk|disk)\w*", "overwrites a block device"),
+        (r":\(\)\s*\{\s*:\s*\|\s*:\s*&\s*\}\s*;\s*:", "fork bomb"),
+        (r"\bchmod\s+(-[a-zA-Z]*\s+)*-[a-zA-Z]*R[a-zA-Z]*\s+\S+\s+/(\s|$)", "recursively changes permissions on /"),
+        (r"\bchown\s+(-[a-zA-Z]*\s+)*-[a-zA-Z]*R[a-zA-Z]*\s+\S+\s+/(\s|$)", "recursively changes ownership of /"),
+        (r"(?i)\bFormat-Volume\b|\bformat\s+[a-z]:", "formats a drive"),
+        (r"(?i)\bRemove-Item\b.*-Recurse.*\s[A-Z]:\\?(\s|$)", "recursively deletes a drive root"),
+    ]
+    .into_iter()
+    .map(|(pattern, reason)| (Regex::new(pattern).expect("valid catastrophic pattern"), reason))
+    .collect()
+});
+
+/// Returns why `command` is considered catastrophic and must be confirmed, if
+/// it is. Commands matching one of the user's `allow_patterns` are exempt.
+pub fn catastrophic_reason(command: &str, settings: Option<&Settings>) -> Option<&'static str> {
+    matched_catastrophic(command).filter(|_| !is_allowed(command, settings))
+}
+
+/// A catastrophic command that `allow_patterns` exempts from confirmation;
+/// callers warn when running one, for audit purposes.
+pub fn allowed_catastrophic_reason(command: &str, settings: Option<&Settings>) -> Option<&'static str> {
+    matched_catastrophic(command).filter(|_| is_allowed(command, settings))
+}
+
+fn matched_catastrophic(command: &str) -> Option<&'static str> {
+    CATASTROPHIC_PATTERNS
+        .iter()
+        .find(|(pattern, _)| pattern.is_match(command))
+        .map(|(_, reason)| *reason)
+}
+
+// Invalid patterns are ignored rather than matched literally: when in doubt, confirm
+fn is_allowed(command: &str, settings: Option<&Settings>) -> bool {
+    settings.is_some_and(|settings| {
+        settings.allow_patterns.iter().any(|pattern| match Regex::new(pattern) {
+            Ok(regex) => regex.is_match(command),
+            Err(e) => {
+                eprintln!("{}", format!("⚠️  Invalid allow_patterns entry '{}': {}. Ignoring it.", pattern, e).yellow());
+                false
+            }
+        })
+    })
+}
+
+// A single plain `rm` (optionally with sudo); anything with pipes, chaining or
+// substitutions is left alone rather than rewritten wrongly
+static SIMPLE_RM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?P<sudo>sudo\s+)?rm(?P<flags>(\s+-\S*)*)\s+(?P<operands>[^;&|<>`$\n]+?)\s*$").expect("valid rm pattern")
+});
+
+/// With `use_trash`, rewrites a plain `rm` so the files go to the trash instead:
+/// through trash-cli when installed, otherwise into a tella-managed trash dir
+/// of its own per run. Like `rm -f`, a forced rewrite skips missing files.
+/// Returns the new command and where the files will end up, or None to run
+/// the original unchanged.
+pub fn make_reversible(command: &str) -> Option<(String, String)> {
+    let caps = SIMPLE_RM.captures(command)?;
+    let sudo = caps.name("sudo").map_or("", |m| m.as_str());
+    let operands = caps.name("operands")?.as_str();
+    let force = caps
+        .name("flags")
+        .is_some_and(|flags| flags.as_str().split_whitespace().any(|flag| flag == "--force" || (!flag.starts_with("--") && flag.contains('f'))));
+
+    if let Some(tool) = ["trash-put", "trash"].into_iter().find(|tool| command_executor::find_in_path(tool).is_some()) {
+        let force = if force { " -f" } else { "" };
+        return Some((format!("{}{}{} -- {}", sudo, tool, force, operands), "the system trash".to_string()));
+    }
+
+    if command_executor::shell_program() == "powershell" {
+        return None;
+    }
+
+    // Unique per run, so two deletions of the same name never meet; the final
+    // mkdir has no -p so a clash fails instead of overwriting
+    let trash = Settings::get_settings_path().ok()?.join("trash");
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    let dir = trash.join(format!("{}-{}-{:09}", clock::now_secs(), std::process::id(), nanos));
+    let destination = dir.display().to_string();
+    let quote = |path: &str| format!("'{}'", path.replace('\'', r"'\''"));
+    let (parent, quoted) = (quote(&trash.display().to_string()), quote(&destination));
+    let mkdir = format!("mkdir -p {parent} && mkdir {quoted}");
+    let reversible = if force {
+        format!("{mkdir} && {{ failed=; for f in {operands}; do [ ! -e \"$f\" ] && [ ! -L \"$f\" ] || {sudo}mv -- \"$f\" {quoted}/ || failed=1; done; [ -z \"$failed\" ]; }}")
+    } else {
+        format!("{mkdir} && {sudo}mv -- {operands} {quoted}/")
+    };
+    Some((reversible, destination))
+}
+
+// A leading plain `sudo`; with options (`sudo -u bob ...`) it changes more
+// than privileges and is kept
+static LEADING_SUDO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*sudo\s+(?P<rest>[^-\s].*)$").expect("valid sudo pattern")
+});
+
+/// With `strip_redundant_sudo` and tella running as root, drops a leading
+/// `sudo`: it adds nothing and fails where sudo isn't installed (containers).
+/// Returns the command to run instead, or None to run it unchanged.
+pub fn strip_redundant_sudo(command: &str, settings: Option<&Settings>) -> Option<String> {
+    if !settings.is_some_and(|s| s.strip_redundant_sudo) || !command_executor::running_as_root() {
+        return None;
+    }
+    let caps = LEADING_SUDO.captures(command)?;
+    Some(caps["rest"].to_string())
+}
+
+/// A minimal POSIX-shell sanity check for model output: unterminated quotes or
+/// backticks, a dangling backslash, unbalanced parentheses or a trailing
+/// operator. Returns one warning per problem; empty means nothing obvious.
+/// Skipped for PowerShell, where quoting and escaping work differently.
+pub fn check_shell_syntax(command: &str) -> Vec<String> {
+    if command_executor::shell_program() == "powershell" {
+        return Vec::new();
+    }
+
+    let mut warnings = Vec::new();
+    let mut single = false;
+    let mut double = false;
+    let mut backtick = false;
+    let mut escaped = false;
+    let mut depth: i32 = 0;
+
+    for c in command.chars() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if !single => escaped = true,
+            '\'' if !double => single = !single,
+            '"' if !single => double = !double,
+            '`' if !single => backtick = !backtick,
+            '(' if !single && !double => depth += 1,
+            ')' if !single && !double => {
+                depth -= 1;
+                if depth < 0 {
+                    warnings.push("closing parenthesis without a matching '('".to_string());
+                    depth = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if single {
+        warnings.push("unterminated single quote (')".to_string());
+    }
+    if double {
+        warnings.push("unterminated double quote (\")".to_string());
+    }
+    if backtick {
+        warnings.push("unterminated backtick (`)".to_string());
+    }
+    if escaped {
+        warnings.push("dangling backslash at the end of the command".to_string());
+    }
+    if depth > 0 {
+        warnings.push("unclosed parenthesis".to_string());
+    }
+
+    let trimmed = command.trim_end();
+    if !single && !double && !escaped && ["|", "&&", "||"].iter().any(|op| trimmed.ends_with(op)) {
+        warnings.push("ends with a dangling operator".to_string());
+    }
+
+    warnings
+}
+
+// Run by the shell itself, so they are never on PATH
+const SHELL_BUILTINS: &[&str] = &[
+    ".", ":", "alias", "bg", "cd", "command", "declare", "echo", "eval", "exec", "exit", "export", "fg",
+    "for", "if", "jobs", "local", "printf", "pwd", "read", "set", "source", "test", "type", "ulimit",
+    "umask", "unalias", "unset", "wait", "while", "[", "[[", "{", "(",
+];
+
+/// Where the program a command starts with comes from.
+pub enum BinaryLookup {
+    Found(String, PathBuf),
+    Missing(String),
+    // Builtins, and commands whose program can't be determined statically
+    Unknown,
+}
+
+/// Resolves the first program of `command` (after `sudo` and `VAR=value`
+/// prefixes) against PATH, e.g. to tell a venv's `python` from the system one.
+pub fn resolve_binary(command: &str) -> BinaryLookup {
+    let is_assignment = |token: &str| {
+        token
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+    };
+    let Some(program) = command
+        .split_whitespace()
+        .find(|token| *token != "sudo" && !is_assignment(token))
+    else {
+        return BinaryLookup::Unknown;
+    };
+
+    // PowerShell cmdlets aren't files, and substitutions or quoting need a real parser
+    let unparseable = program.contains(['$', '`', '\'', '"']);
+    if command_executor::shell_program() == "powershell" || unparseable || SHELL_BUILTINS.contains(&program) {
+        return BinaryLookup::Unknown;
+    }
+
+    match command_executor::find_in_path(program) {
+        Some(path) => BinaryLookup::Found(program.to_string(), path),
+        None => BinaryLookup::Missing(program.to_string()),
+    }
+}
@@ -1,8 +1,11 @@
+use crate::api::{self, ExplainFormat};
 use colored::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputSettings {
@@ -10,6 +13,23 @@ pub struct OutputSettings {
     pub show_description: bool,
     pub show_explanation: bool,
     pub show_severity: bool,
+    #[serde(default)]
+    pub output_order: Vec<String>, // e.g. ["severity", "command", "description"]; empty keeps the default
+    #[serde(default)]
+    pub highlight_command: bool, // color the command's program, flags and paths
+    #[serde(default)]
+    pub min_severity_display: Option<String>, // hide the severity line below this: "safe" (default), "warning" or "dangerous"
+    #[serde(default)]
+    pub severity_display: HashMap<String, SeverityStyle>, // "safe", "warning", "dangerous" or "unknown" -> emoji and color
+}
+
+/// How one severity level is shown; unset parts keep the built-in look.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SeverityStyle {
+    #[serde(default)]
+    pub emoji: Option<String>, // "" hides the emoji
+    #[serde(default)]
+    pub color: Option<String>, // a color name such as "blue" or "bright magenta"
 }
 
 impl Default for OutputSettings {
@@ -19,20 +39,277 @@ impl Default for OutputSettings {
             show_description: true,
             show_explanation: true,
             show_severity: true,
+            output_order: Vec::new(),
+            highlight_command: false,
+            min_severity_display: None,
+            severity_display: HashMap::new(),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Settings {
-    pub provider: String, // "ollama" or "cerebras"
+    #[serde(default)]
+    pub provider: String, // "ollama", "cerebras", "openai", "anthropic", "groq", "gemini", "mistral" or "lmstudio"; inferred from the credentials when empty
     pub cerebras_api_key: Option<String>,
     pub ollama_model: Option<String>,
     pub ollama_base_url: Option<String>,
     #[serde(default)]
     pub output_settings: OutputSettings,
+    #[serde(default)]
+    pub use_cache: bool,
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>, // defaults to 24 hours
+    #[serde(default)]
+    pub normalize_prompt: bool, // send the normalized question to the model
+    #[serde(default)]
+    pub blocked_commands: Vec<String>, // regexes tella refuses to execute
+    #[serde(default)]
+    pub prompt_templates: HashMap<String, String>, // model name (or prefix) -> prompt template
+    #[serde(default)]
+    pub auto_run_max_severity: Option<String>, // highest severity --yes runs without asking, defaults to "warning"
+    #[serde(default)]
+    pub disable_history: bool,
+    #[serde(default)]
+    pub use_keyring: bool, // keep API keys in the OS keyring instead of settings.json
+    #[serde(default)]
+    pub detect_direct_commands: bool, // offer to run/explain input that is already a command
+    #[serde(default)]
+    pub prewarm: bool, // load the Ollama model in the background at startup
+    #[serde(default)]
+    pub update_snooze_days: Option<u64>, // hide the update banner for N days once shown
+    #[serde(default)]
+    pub thinking_message: Option<String>, // spinner text, "Thinking..." when unset
+    #[serde(default)]
+    pub ollama_think: Option<bool>, // enable/disable reasoning on models that support it
+    #[serde(default)]
+    pub output_filter_lines: Option<usize>, // offer to filter command output longer than this
+    #[serde(default)]
+    pub use_trash: bool, // turn plain `rm` into a move to the trash
+    #[serde(default)]
+    pub explanation_format: Option<ExplainFormat>, // "prose", "bullets" or "steps"
+    #[serde(default)]
+    pub interactive_programs: Vec<String>, // extra programs that get the terminal instead of captured output
+    #[serde(default)]
+    pub dangerous_confirm_type: Option<String>, // "menu" (default), "type-yes" or "type-command"
+    #[serde(default)]
+    pub extra_params: serde_json::Map<String, serde_json::Value>, // merged into provider requests (top_p, seed, ...); num_ctx and other Ollama options go to Ollama only
+    #[serde(default)]
+    pub inline_explanation: bool, // show a one-line rationale under the command
+    #[serde(default)]
+    pub system_prompt: Option<String>, // replaces the default JSON-only system prompt
+    #[serde(default)]
+    pub model_catalog_url: Option<String>, // team-curated model list used by --settings
+    #[serde(default)]
+    pub history_max_entries: Option<usize>, // defaults to 10000, 0 keeps everything
+    #[serde(default)]
+    pub context_window: Option<usize>, // tokens the model accepts; extra_params.num_ctx wins, else the provider default
+    #[serde(default)]
+    pub use_pager: bool, // page long output and explanations through $PAGER, like --pager
+    #[serde(default)]
+    pub resolve_binary_path: bool, // show which binary on PATH the command will run
+    #[serde(default)]
+    pub table_output: bool, // show CSV/TSV command output as an aligned table
+    #[serde(default)]
+    pub exec_wrapper: Option<String>, // run commands inside e.g. "firejail --net=none" or "docker run --rm -i img sh -c"
+    #[serde(default)]
+    pub allow_patterns: Vec<String>, // regexes for catastrophic commands that may run without the forced confirmation
+    #[serde(default)]
+    pub consensus_models: Vec<String>, // models --consensus asks, e.g. ["ollama/llama3.2", "cerebras/llama3.3-70b"]
+    #[serde(default)]
+    pub help_exploration: bool, // answer "what are the options for X" with read-only X --help / man X commands
+    #[serde(default)]
+    pub check_prerequisites: bool, // ask for the tools/services a command needs and check them before running
+    #[serde(default)]
+    pub openai_api_key: Option<String>,
+    #[serde(default)]
+    pub openai_model: Option<String>, // Ollama uses ollama_model
+    #[serde(default)]
+    pub anthropic_api_key: Option<String>,
+    #[serde(default)]
+    pub anthropic_model: Option<String>,
+    #[serde(default)]
+    pub groq_api_key: Option<String>,
+    #[serde(default)]
+    pub groq_model: Option<String>, // defaults to llama-3.3-70b-versatile
+    #[serde(default)]
+    pub gemini_api_key: Option<String>,
+    #[serde(default)]
+    pub gemini_model: Option<String>,
+    #[serde(default)]
+    pub ollama_json_format: Option<bool>, // send "format": "json" with suggestions; unset = on except for reasoning models
+    #[serde(default)]
+    pub mistral_api_key: Option<String>,
+    #[serde(default)]
+    pub mistral_model: Option<String>,
+    #[serde(default)]
+    pub terminal_window: Option<String>, // adds "Open in new window": "auto" detects the terminal, or name its program
+    #[serde(default)]
+    pub strip_redundant_sudo: bool, // drop a leading sudo when tella already runs as root
+    #[serde(default)]
+    pub lmstudio_base_url: Option<String>, // http://localhost:1234 when unset
+    #[serde(default)]
+    pub lmstudio_model: Option<String>,
+    #[serde(default)]
+    pub learn_mode: bool, // "Quiz me" in the menu and a daily reminder when quiz cards are due
+    #[serde(default)]
+    pub cerebras_model: Option<String>, // older settings files keep it in ollama_model
+    #[serde(default)]
+    pub fallback_providers: Vec<String>, // tried in order when the provider can't be reached
+    #[serde(default)]
+    pub normalize_paths: bool, // rewrite path separators for the execution shell, e.g. .\src\app.js -> ./src/app.js in bash
+}
+
+/// One finding of `--validate-config`.
+pub struct ConfigProblem {
+    pub error: bool, // `load` would fail; otherwise a warning
+    pub message: String,
+    pub context: Option<(usize, String)>, // line number and text in settings.json
+}
+
+// The line of the first `"key":` in the file, for pointing at a problem
+fn line_of_key(content: &str, key: &str) -> Option<usize> {
+    let pattern = regex::Regex::new(&format!(r#""{}"\s*:"#, regex::escape(key))).ok()?;
+    let offset = pattern.find(content)?.start();
+    Some(content[..offset].matches('\n').count() + 1)
+}
+
+/// The known model `input` most likely meant: the shortest one it's a prefix
+/// of (so "llama3" finds "llama3.3-70b"), else the nearest by edit distance
+/// when the typo is small enough to be one.
+fn closest_model<'a>(input: &str, models: &'a [String]) -> Option<&'a str> {
+    let input = input.to_lowercase();
+    if input.is_empty() {
+        return None;
+    }
+
+    if let Some(prefixed) = models
+        .iter()
+        .filter(|m| m.to_lowercase().starts_with(&input))
+        .min_by_key(|m| m.len())
+    {
+        return Some(prefixed);
+    }
+
+    let max_distance = (input.chars().count() / 3).max(2);
+    models
+        .iter()
+        .map(|m| (m, edit_distance(&input, &m.to_lowercase())))
+        .filter(|(_, distance)| *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(m, _)| m.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// The setup menu's providers, in menu order
+const MENU_PROVIDERS: [&str; 8] = ["ollama", "cerebras", "openai", "anthropic", "groq", "gemini", "mistral", "lmstudio"];
+
+const KEYRING_SERVICE: &str = "tella";
+const CEREBRAS_KEYRING_USER: &str = "cerebras_api_key";
+const OPENAI_KEYRING_USER: &str = "openai_api_key";
+const ANTHROPIC_KEYRING_USER: &str = "anthropic_api_key";
+const GROQ_KEYRING_USER: &str = "groq_api_key";
+const GEMINI_KEYRING_USER: &str = "gemini_api_key";
+const MISTRAL_KEYRING_USER: &str = "mistral_api_key";
+
+fn keyring_get(user: &str) -> Result<String, String> {
+    keyring::Entry::new(KEYRING_SERVICE, user)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| e.to_string())
+}
+
+fn keyring_set(user: &str, secret: &str) -> Result<(), String> {
+    keyring::Entry::new(KEYRING_SERVICE, user)
+        .and_then(|entry| entry.set_password(secret))
+        .map_err(|e| e.to_string())
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// `--config-path`: keep settings, history and the cache in `dir`.
+pub fn set_config_path(dir: PathBuf) {
+    let _ = CONFIG_PATH_OVERRIDE.set(dir);
+}
+
+// The directory (or a parent) is read-only, e.g. a locked-down managed machine
+fn is_read_only(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem)
+}
+
+/// An advisory lock on a settings directory, held from reading settings.json to
+/// writing it back so concurrent `--settings` runs can't interleave. Released
+/// when dropped.
+pub struct SettingsLock {
+    _file: fs::File,
+}
+
+impl SettingsLock {
+    fn acquire(settings_dir: &Path) -> Result<SettingsLock, (&'static str, io::Error)> {
+        fs::create_dir_all(settings_dir)
+            .map_err(|e| ("Failed to create settings directory", e))?;
+        let file = fs::File::create(settings_dir.join("settings.json.lock"))
+            .map_err(|e| ("Failed to create settings lock file", e))?;
+
+        if let Err(fs::TryLockError::WouldBlock) = file.try_lock() {
+            println!("{}", "⏳ Waiting for another tella to finish writing its settings...".dimmed());
+        }
+        file.lock()
+            .map_err(|e| ("Failed to lock settings file", e))?;
+        Ok(SettingsLock { _file: file })
+    }
 }
 
+pub const OPENAI_MODELS: &[&str] = &[
+    "gpt-4o",
+    "gpt-4o-mini",
+    "gpt-4-turbo",
+    "gpt-3.5-turbo",
+];
+
+pub const ANTHROPIC_MODELS: &[&str] = &[
+    "claude-sonnet-4-5",
+    "claude-opus-4-5",
+    "claude-haiku-4-5",
+];
+
+pub const GROQ_MODELS: &[&str] = &[
+    "llama-3.3-70b-versatile",
+    "mixtral-8x7b-32768",
+    "llama-3.1-8b-instant",
+];
+
+pub const GEMINI_MODELS: &[&str] = &[
+    "gemini-2.0-flash",
+    "gemini-1.5-pro",
+    "gemini-1.5-flash",
+];
+
+pub const MISTRAL_MODELS: &[&str] = &[
+    "mistral-large-latest",
+    "mistral-small-latest",
+    "codestral-latest",
+];
+
+/// LM Studio's local server address unless `lmstudio_base_url` is set.
+pub const DEFAULT_LMSTUDIO_URL: &str = "http://localhost:1234";
+
 pub const CEREBRAS_MODELS: &[&str] = &[
     "llama3.3-70b",
     "llama3.1-8b",
@@ -42,105 +319,805 @@ pub const CEREBRAS_MODELS: &[&str] = &[
     "qwen-3-coder-480b",
 ];
 
+/// Approved models published by an org at `model_catalog_url`, e.g.
+/// `{"cerebras": ["llama3.3-70b", "gpt-oss-120b"], "openai": ["gpt-4o"]}`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModelCatalog {
+    #[serde(default)]
+    cerebras: Vec<String>,
+    #[serde(default)]
+    openai: Vec<String>,
+    #[serde(default)]
+    anthropic: Vec<String>,
+    #[serde(default)]
+    groq: Vec<String>,
+    #[serde(default)]
+    gemini: Vec<String>,
+    #[serde(default)]
+    mistral: Vec<String>,
+}
+
 impl Settings {
-    pub fn get_settings_path() -> PathBuf {
+    pub fn get_settings_path() -> Result<PathBuf, String> {
+        if let Some(dir) = CONFIG_PATH_OVERRIDE.get() {
+            return Ok(dir.clone());
+        }
+
         let app_data = if cfg!(target_os = "windows") {
-            dirs::data_dir().unwrap_or_else(|| PathBuf::from("."))
+            dirs::data_dir()
         } else {
-            dirs::config_dir().unwrap_or_else(|| PathBuf::from("."))
+            dirs::config_dir()
         };
 
-        let tella_dir = app_data.join("tella");
-        tella_dir
+        Self::resolve_settings_path(app_data, dirs::home_dir())
+    }
+
+    // Prefer the platform config dir, then ~/.tella; never fall back to the cwd.
+    // A ~/.tella that has settings wins over a platform dir that has none, which
+    // is where setup saves when the platform dir is read-only.
+    fn resolve_settings_path(app_data: Option<PathBuf>, home: Option<PathBuf>) -> Result<PathBuf, String> {
+        match (app_data, home) {
+            (Some(app_data), Some(home))
+                if !app_data.join("tella").join("settings.json").exists()
+                    && home.join(".tella").join("settings.json").exists() =>
+            {
+                Ok(home.join(".tella"))
+            }
+            (Some(app_data), _) => Ok(app_data.join("tella")),
+            (None, Some(home)) => Ok(home.join(".tella")),
+            (None, None) => Err("Could not determine a config directory. Set HOME so tella can store its settings in $HOME/.tella.".to_string()),
+        }
+    }
+
+    pub fn get_settings_file() -> Result<PathBuf, String> {
+        Ok(Self::get_settings_path()?.join("settings.json"))
+    }
+
+    /// True when there is a settings file or the environment selects a provider.
+    pub fn is_configured() -> bool {
+        Self::configured_from_env()
+            || Self::get_settings_file().is_ok_and(|path| path.exists())
     }
 
-    pub fn get_settings_file() -> PathBuf {
-        Self::get_settings_path().join("settings.json")
+    // Enough in the environment to run without settings.json (e.g. CI secrets)
+    fn configured_from_env() -> bool {
+        ["TELLA_PROVIDER", "CEREBRAS_API_KEY", "OPENAI_API_KEY", "ANTHROPIC_API_KEY", "GROQ_API_KEY", "GEMINI_API_KEY", "MISTRAL_API_KEY", "TELLA_OLLAMA_URL", "TELLA_LMSTUDIO_URL"]
+            .iter()
+            .any(|name| env_var(name).is_some())
     }
 
     pub fn load() -> Result<Settings, String> {
-        let settings_file = Self::get_settings_file();
+        let settings_file = Self::get_settings_file()?;
 
-        if !settings_file.exists() {
-            return Err("Settings file not found. Run 'tella --settings' to configure.".to_string());
+        let mut settings: Settings = if settings_file.exists() {
+            let content = fs::read_to_string(&settings_file)
+                .map_err(|e| format!("Failed to read settings file: {}", e))?;
+
+            let mut settings: Settings = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse settings file: {}", e))?;
+            settings.load_keyring_secrets();
+            settings.migrate_cerebras_model();
+            settings
+        } else if Self::configured_from_env() {
+            Settings::default()
+        } else {
+            return Err("Settings file not found. Run 'tella --settings' to configure, or set TELLA_PROVIDER or CEREBRAS_API_KEY.".to_string());
+        };
+
+        settings.apply_env_overrides();
+        if settings.provider.is_empty() {
+            settings.infer_provider()?;
+        }
+        settings.apply_env_model();
+
+        if let Some((_, problem)) = settings.check().into_iter().next() {
+            return Err(problem);
+        }
+
+        Ok(settings)
+    }
+
+    // Cerebras used to share ollama_model with Ollama, which can't work once
+    // both are in a fallback chain. Applied to the file as written, before
+    // the environment can change the provider.
+    fn migrate_cerebras_model(&mut self) {
+        if self.provider == "cerebras" && self.cerebras_model.is_none() {
+            self.cerebras_model = self.ollama_model.clone();
         }
+    }
 
-        let content = fs::read_to_string(&settings_file)
-            .map_err(|e| format!("Failed to read settings file: {}", e))?;
+    // What makes load fail once the provider is known, as (settings key, message)
+    fn check(&self) -> Vec<(&'static str, String)> {
+        let mut problems = self.provider_problems(&self.provider);
 
-        let settings: Settings = serde_json::from_str(&content)
-            .map_err(|e| format!("Failed to parse settings file: {}", e))?;
+        if let Some((key, _)) = self.extra_params.iter().find(|(_, value)| value.is_object() || value.is_array()) {
+            problems.push(("extra_params", format!("Invalid extra_params in settings: '{}' must be a number, string or boolean.", key)));
+        }
+
+        if let Some(confirm_type) = self.dangerous_confirm_type.as_deref() {
+            if !["menu", "type-yes", "type-command"].contains(&confirm_type) {
+                problems.push(("dangerous_confirm_type", format!("Invalid dangerous_confirm_type '{}' in settings. Must be 'menu', 'type-yes' or 'type-command'.", confirm_type)));
+            }
+        }
 
-        // Validate based on provider
-        match settings.provider.as_str() {
+        if let Some(min_severity) = self.output_settings.min_severity_display.as_deref() {
+            if !["safe", "warning", "dangerous"].contains(&min_severity) {
+                problems.push(("min_severity_display", format!("Invalid min_severity_display '{}' in settings. Must be 'safe', 'warning' or 'dangerous'.", min_severity)));
+            }
+        }
+
+        problems
+    }
+
+    // Missing credentials or model for `provider` (the active one or a fallback)
+    fn provider_problems(&self, provider: &str) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+
+        match provider {
             "cerebras" => {
-                if settings.cerebras_api_key.is_none() || settings.cerebras_api_key.as_ref().map_or(true, |k| k.is_empty()) {
-                    return Err("CEREBRAS_API_KEY is not configured. Run 'tella --settings' to set it up.".to_string());
+                if self.cerebras_api_key.as_ref().is_none_or(|k| k.is_empty()) {
+                    problems.push(("cerebras_api_key", "CEREBRAS_API_KEY is not configured. Export CEREBRAS_API_KEY or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+                if self.cerebras_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("cerebras_model", "Cerebras model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+            }
+            "openai" => {
+                if self.openai_api_key.as_ref().is_none_or(|k| k.is_empty()) {
+                    problems.push(("openai_api_key", "OPENAI_API_KEY is not configured. Export OPENAI_API_KEY or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+                if self.openai_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("openai_model", "OpenAI model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+            }
+            "anthropic" => {
+                if self.anthropic_api_key.as_ref().is_none_or(|k| k.is_empty()) {
+                    problems.push(("anthropic_api_key", "ANTHROPIC_API_KEY is not configured. Export ANTHROPIC_API_KEY or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+                if self.anthropic_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("anthropic_model", "Anthropic model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+            }
+            "groq" => {
+                if self.groq_api_key.as_ref().is_none_or(|k| k.is_empty()) {
+                    problems.push(("groq_api_key", "GROQ_API_KEY is not configured. Export GROQ_API_KEY or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+            }
+            "gemini" => {
+                if self.gemini_api_key.as_ref().is_none_or(|k| k.is_empty()) {
+                    problems.push(("gemini_api_key", "GEMINI_API_KEY is not configured. Export GEMINI_API_KEY or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+                if self.gemini_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("gemini_model", "Gemini model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+            }
+            "mistral" => {
+                if self.mistral_api_key.as_ref().is_none_or(|k| k.is_empty()) {
+                    problems.push(("mistral_api_key", "MISTRAL_API_KEY is not configured. Export MISTRAL_API_KEY or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+                if self.mistral_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("mistral_model", "Mistral model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
+                }
+            }
+            // LM Studio takes no API key
+            "lmstudio" => {
+                if self.lmstudio_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("lmstudio_model", "LM Studio model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
                 }
             }
             "ollama" => {
-                if settings.ollama_model.is_none() || settings.ollama_model.as_ref().map_or(true, |m| m.is_empty()) {
-                    return Err("Ollama model is not configured. Run 'tella --settings' to set it up.".to_string());
+                if self.ollama_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    problems.push(("ollama_model", "Ollama model is not configured. Export TELLA_MODEL or run 'tella --settings' (environment variables override settings.json).".to_string()));
                 }
             }
-            _ => return Err("Invalid provider in settings. Must be 'ollama' or 'cerebras'.".to_string()),
+            _ => problems.push(("provider", "Invalid provider in settings. Must be 'ollama', 'cerebras', 'openai', 'anthropic', 'groq', 'gemini', 'mistral' or 'lmstudio' (check TELLA_PROVIDER, which overrides settings.json).".to_string())),
         }
 
-        Ok(settings)
+        problems
+    }
+
+    // Values tella runs with but that are probably mistakes, as (settings key, message)
+    fn lint(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+
+        if let Some(temperature) = self.extra_params.get("temperature") {
+            if !temperature.as_f64().is_some_and(|t| (0.0..=2.0).contains(&t)) {
+                problems.push(("temperature", format!("extra_params.temperature is {}; expected a number from 0 to 2.", temperature)));
+            }
+        }
+        for key in ["num_ctx", "num_predict", "max_tokens"] {
+            if let Some(value) = self.extra_params.get(key) {
+                if value.as_u64().is_none_or(|n| n == 0) {
+                    problems.push((key, format!("extra_params.{} is {}; expected a positive whole number.", key, value)));
+                }
+            }
+        }
+
+        let zero_disables = [
+            ("cache_ttl_secs", self.cache_ttl_secs, "every cached answer expires at once"),
+            ("context_window", self.context_window.map(|n| n as u64), "no room is left for context"),
+            ("output_filter_lines", self.output_filter_lines.map(|n| n as u64), "every output is offered for filtering"),
+        ];
+        for (key, value, effect) in zero_disables {
+            if value == Some(0) {
+                problems.push((key, format!("{} is 0, so {}.", key, effect)));
+            }
+        }
+
+        for (key, patterns) in [("blocked_commands", &self.blocked_commands), ("allow_patterns", &self.allow_patterns)] {
+            for pattern in patterns {
+                if let Err(e) = regex::Regex::new(pattern) {
+                    problems.push((key, format!("{} pattern '{}' is not a valid regex: {}", key, pattern, e.to_string().lines().last().unwrap_or_default())));
+                }
+            }
+        }
+
+        for field in &self.output_settings.output_order {
+            if !["command", "severity", "description", "explanation"].contains(&field.as_str()) {
+                problems.push(("output_order", format!("output_order has unknown field '{}'. Use command, severity, description or explanation.", field)));
+            }
+        }
+
+        for (level, style) in &self.output_settings.severity_display {
+            if !["safe", "warning", "dangerous", "unknown"].contains(&level.as_str()) {
+                problems.push(("severity_display", format!("severity_display has unknown level '{}'. Use safe, warning, dangerous or unknown.", level)));
+            }
+            if let Some(color) = style.color.as_deref().filter(|c| c.parse::<Color>().is_err()) {
+                problems.push(("severity_display", format!("severity_display.{}.color '{}' is not a color name; the default color is used.", level, color)));
+            }
+        }
+
+        for (key, url) in [("ollama_base_url", &self.ollama_base_url), ("lmstudio_base_url", &self.lmstudio_base_url), ("model_catalog_url", &self.model_catalog_url)] {
+            if let Some(url) = url.as_deref().filter(|url| !url.starts_with("http://") && !url.starts_with("https://")) {
+                problems.push((key, format!("{} '{}' should start with http:// or https://.", key, url)));
+            }
+        }
+
+        for fallback in &self.fallback_providers {
+            if *fallback == self.provider {
+                problems.push(("fallback_providers", format!("fallback_providers lists the primary provider '{}'; it is skipped.", fallback)));
+                continue;
+            }
+            for (key, message) in self.provider_problems(fallback) {
+                let message = if key == "provider" {
+                    format!("fallback_providers has unknown provider '{}'. Use ollama, cerebras, openai, anthropic, groq, gemini, mistral or lmstudio.", fallback)
+                } else {
+                    format!("Fallback '{}' can't be used: {}", fallback, message)
+                };
+                problems.push(("fallback_providers", message));
+            }
+        }
+
+        if self.consensus_models.len() == 1 {
+            problems.push(("consensus_models", "consensus_models lists one model; --consensus needs at least two.".to_string()));
+        }
+
+        problems
+    }
+
+    /// `tella --validate-config`: every problem in settings.json instead of the
+    /// first one `load` stops at. Checks the JSON syntax, unknown keys, value
+    /// types, what `load` requires (after environment overrides, as a real run
+    /// sees it) and suspicious values.
+    pub fn validate_file() -> Result<(PathBuf, Vec<ConfigProblem>), String> {
+        let path = Self::get_settings_file()?;
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Can't read {}: {}. Run 'tella --settings' to create it.", path.display(), e))?;
+
+        let at_line = |line: usize| {
+            let text = content.lines().nth(line.saturating_sub(1)).map(|text| text.trim_end().to_string());
+            (line > 0).then_some(line).zip(text)
+        };
+        let problem = |error: bool, key: &str, message: String| ConfigProblem {
+            error,
+            message,
+            context: line_of_key(&content, key).and_then(at_line),
+        };
+
+        let value: serde_json::Value = match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(e) => {
+                let message = format!("Invalid JSON: {}", e);
+                return Ok((path, vec![ConfigProblem { error: true, message, context: at_line(e.line()) }]));
+            }
+        };
+        let Some(object) = value.as_object() else {
+            return Ok((path, vec![ConfigProblem { error: true, message: "The settings must be a JSON object ({ ... }).".to_string(), context: None }]));
+        };
+
+        let mut problems = Vec::new();
+
+        // The known keys are whatever the defaults serialize to
+        let known = |value: serde_json::Value| -> Vec<String> {
+            value.as_object().map(|o| o.keys().cloned().collect()).unwrap_or_default()
+        };
+        let known_settings = known(serde_json::to_value(Settings::default()).unwrap_or_default());
+        let known_output = known(serde_json::to_value(OutputSettings::default()).unwrap_or_default());
+        let output_object = object.get("output_settings").and_then(|o| o.as_object());
+        let keys = object
+            .keys()
+            .map(|key| (key, &known_settings))
+            .chain(output_object.into_iter().flat_map(|o| o.keys()).map(|key| (key, &known_output)));
+        for (key, known) in keys {
+            if !known.contains(key) {
+                let hint = closest_model(key, known).map(|k| format!(" Did you mean '{}'?", k)).unwrap_or_default();
+                problems.push(problem(false, key, format!("Unknown setting '{}' is ignored.{}", key, hint)));
+            }
+        }
+
+        let mut settings: Settings = match serde_json::from_str(&content) {
+            Ok(settings) => settings,
+            Err(e) => {
+                problems.push(ConfigProblem { error: true, message: format!("Wrong value type: {}", e), context: at_line(e.line()) });
+                return Ok((path, problems));
+            }
+        };
+
+        settings.load_keyring_secrets();
+        settings.migrate_cerebras_model();
+        settings.apply_env_overrides();
+        if settings.provider.is_empty() {
+            if let Err(e) = settings.infer_provider() {
+                problems.push(problem(true, "provider", e));
+            }
+        }
+        settings.apply_env_model();
+        if !settings.provider.is_empty() {
+            problems.extend(settings.check().into_iter().map(|(key, message)| problem(true, key, message)));
+        }
+        problems.extend(settings.lint().into_iter().map(|(key, message)| problem(false, key, message)));
+
+        Ok((path, problems))
     }
 
-    pub fn save(&self) -> Result<(), String> {
-        let settings_dir = Self::get_settings_path();
-        let settings_file = Self::get_settings_file();
+    // No provider set: use the one whose credentials are present. Ollama and LM Studio need a
+    // base URL to count, since their default URLs would otherwise always match.
+    fn infer_provider(&mut self) -> Result<(), String> {
+        let present = |value: &Option<String>| value.as_ref().is_some_and(|v| !v.is_empty());
+        let configured: Vec<&str> = [
+            ("cerebras", present(&self.cerebras_api_key)),
+            ("openai", present(&self.openai_api_key)),
+            ("anthropic", present(&self.anthropic_api_key)),
+            ("groq", present(&self.groq_api_key)),
+            ("gemini", present(&self.gemini_api_key)),
+            ("mistral", present(&self.mistral_api_key)),
+            ("ollama", present(&self.ollama_base_url)),
+            ("lmstudio", present(&self.lmstudio_base_url)),
+        ]
+        .into_iter()
+        .filter_map(|(provider, configured)| configured.then_some(provider))
+        .collect();
 
-        fs::create_dir_all(&settings_dir)
-            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+        match configured.as_slice() {
+            ["cerebras"] => {
+                self.provider = "cerebras".to_string();
+                if self.cerebras_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    self.cerebras_model = Some(CEREBRAS_MODELS[0].to_string());
+                }
+                Ok(())
+            }
+            ["openai"] => {
+                self.provider = "openai".to_string();
+                if self.openai_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    self.openai_model = Some(OPENAI_MODELS[0].to_string());
+                }
+                Ok(())
+            }
+            ["anthropic"] => {
+                self.provider = "anthropic".to_string();
+                if self.anthropic_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    self.anthropic_model = Some(ANTHROPIC_MODELS[0].to_string());
+                }
+                Ok(())
+            }
+            ["groq"] => {
+                self.provider = "groq".to_string();
+                Ok(())
+            }
+            ["gemini"] => {
+                self.provider = "gemini".to_string();
+                if self.gemini_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    self.gemini_model = Some(GEMINI_MODELS[0].to_string());
+                }
+                Ok(())
+            }
+            ["mistral"] => {
+                self.provider = "mistral".to_string();
+                if self.mistral_model.as_ref().is_none_or(|m| m.is_empty()) {
+                    self.mistral_model = Some(MISTRAL_MODELS[0].to_string());
+                }
+                Ok(())
+            }
+            ["lmstudio"] => {
+                self.provider = "lmstudio".to_string();
+                Ok(())
+            }
+            ["ollama"] => {
+                self.provider = "ollama".to_string();
+                Ok(())
+            }
+            [] => Err("No provider is configured. Export CEREBRAS_API_KEY, OPENAI_API_KEY, ANTHROPIC_API_KEY, GROQ_API_KEY, GEMINI_API_KEY or MISTRAL_API_KEY, set TELLA_PROVIDER, or run 'tella --settings'.".to_string()),
+            several => Err(format!("Several providers are configured ({}) but no provider is set. Set TELLA_PROVIDER or 'provider' in settings.json.", several.join(", "))),
+        }
+    }
 
-        let content = serde_json::to_string_pretty(&self)
-            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    // Precedence: environment > settings.json
+    fn apply_env_overrides(&mut self) {
+        if let Some(provider) = env_var("TELLA_PROVIDER") {
+            self.provider = provider.to_lowercase();
+        }
+        if let Some(url) = env_var("TELLA_LMSTUDIO_URL") {
+            self.lmstudio_base_url = Some(url);
+        }
+        if let Some(url) = env_var("TELLA_OLLAMA_URL") {
+            self.ollama_base_url = Some(url);
+        }
+        if let Some(key) = env_var("CEREBRAS_API_KEY") {
+            self.cerebras_api_key = Some(key);
+        }
+        if let Some(key) = env_var("OPENAI_API_KEY") {
+            self.openai_api_key = Some(key);
+        }
+        if let Some(key) = env_var("ANTHROPIC_API_KEY") {
+            self.anthropic_api_key = Some(key);
+        }
+        if let Some(key) = env_var("GROQ_API_KEY") {
+            self.groq_api_key = Some(key);
+        }
+        if let Some(key) = env_var("GEMINI_API_KEY") {
+            self.gemini_api_key = Some(key);
+        }
+        if let Some(key) = env_var("MISTRAL_API_KEY") {
+            self.mistral_api_key = Some(key);
+        }
+    }
 
-        fs::write(&settings_file, content)
-            .map_err(|e| format!("Failed to write settings file: {}", e))?;
+    // TELLA_MODEL names a model of the primary provider, so it is applied once the
+    // provider is resolved; the fallbacks keep their own models
+    fn apply_env_model(&mut self) {
+        let Some(model) = env_var("TELLA_MODEL") else {
+            return;
+        };
+        let slot = match self.provider.as_str() {
+            "cerebras" => &mut self.cerebras_model,
+            "openai" => &mut self.openai_model,
+            "anthropic" => &mut self.anthropic_model,
+            "groq" => &mut self.groq_model,
+            "gemini" => &mut self.gemini_model,
+            "mistral" => &mut self.mistral_model,
+            "lmstudio" => &mut self.lmstudio_model,
+            _ => &mut self.ollama_model,
+        };
+        *slot = Some(model);
+    }
+
+    /// The model for the active provider.
+    /// Groq falls back to its first model when none is set.
+    pub fn model(&self) -> Option<&str> {
+        let model = match self.provider.as_str() {
+            "cerebras" => self.cerebras_model.as_deref(),
+            "openai" => self.openai_model.as_deref(),
+            "anthropic" => self.anthropic_model.as_deref(),
+            "gemini" => self.gemini_model.as_deref(),
+            "mistral" => self.mistral_model.as_deref(),
+            "lmstudio" => self.lmstudio_model.as_deref(),
+            "groq" => self.groq_model.as_deref().filter(|m| !m.is_empty()).or(Some(GROQ_MODELS[0])),
+            _ => self.ollama_model.as_deref(),
+        };
+        model.filter(|m| !m.is_empty())
+    }
+
+    fn save_error(settings_dir: &Path, what: &str, e: &io::Error) -> String {
+        if is_read_only(e) {
+            format!(
+                "Can't write settings to {} ({}). Run with --config-path <DIR> to use a writable directory, or fix the permissions on {}.",
+                settings_dir.display(),
+                e,
+                settings_dir.display()
+            )
+        } else {
+            format!("{}: {}", what, e)
+        }
+    }
+
+    // On failure, returns what was being done along with the IO error so
+    // callers can tell a read-only location apart from other failures.
+    // The caller holds the directory's SettingsLock.
+    fn save_in(&self, settings_dir: &Path) -> Result<(), (&'static str, io::Error)> {
+        let settings_file = settings_dir.join("settings.json");
+
+        fs::create_dir_all(settings_dir)
+            .map_err(|e| ("Failed to create settings directory", e))?;
+
+        let mut to_write = self.clone();
+        if self.use_keyring {
+            to_write.store_keyring_secrets();
+        }
+
+        let content = serde_json::to_string_pretty(&to_write)
+            .map_err(|e| ("Failed to serialize settings", io::Error::other(e)))?;
+
+        // Write a temp file and rename it over settings.json, so an interrupted
+        // write never leaves a truncated config behind
+        let temp_file = settings_dir.join(format!("settings.json.{}.tmp", std::process::id()));
+        if let Err(e) = fs::write(&temp_file, content) {
+            let _ = fs::remove_file(&temp_file);
+            return Err(("Failed to write settings file", e));
+        }
+        fs::rename(&temp_file, &settings_file).map_err(|e| {
+            let _ = fs::remove_file(&temp_file);
+            ("Failed to write settings file", e)
+        })?;
 
         Ok(())
     }
 
+    /// Writes settings.json and returns its path, under the lock taken when the
+    /// settings were read. If the config dir is read-only (so `lock` is None),
+    /// offers to save in ~/.tella instead when the next run would pick it up
+    /// (no --config-path, no settings.json in the way).
+    pub fn save(&self, _lock: Option<&SettingsLock>) -> Result<PathBuf, String> {
+        let settings_dir = Self::get_settings_path()?;
+        let (what, e) = match self.save_in(&settings_dir) {
+            Ok(()) => return Ok(settings_dir.join("settings.json")),
+            Err(err) => err,
+        };
+
+        let fallback = dirs::home_dir()
+            .map(|home| home.join(".tella"))
+            .filter(|fallback| {
+                is_read_only(&e)
+                    && CONFIG_PATH_OVERRIDE.get().is_none()
+                    && *fallback != settings_dir
+                    && !settings_dir.join("settings.json").exists()
+            });
+        let Some(fallback) = fallback else {
+            return Err(Self::save_error(&settings_dir, what, &e));
+        };
+
+        println!();
+        println!("{}", format!("⚠️  {} is not writable ({}).", settings_dir.display(), e).yellow());
+        print!("{} ", format!("Save settings to {} instead? (Y/n):", fallback.display()).bold());
+        io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
+
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+        if answer.trim().eq_ignore_ascii_case("n") {
+            return Err(Self::save_error(&settings_dir, what, &e));
+        }
+
+        let _fallback_lock = SettingsLock::acquire(&fallback)
+            .map_err(|(what, e)| Self::save_error(&fallback, what, &e))?;
+        self.save_in(&fallback)
+            .map_err(|(what, e)| Self::save_error(&fallback, what, &e))?;
+        Ok(fallback.join("settings.json"))
+    }
+
+    // Keys missing from settings.json are looked up in the keyring; env overrides still win.
+    // Once the provider is known only its key and the fallbacks' keys are looked up.
+    fn load_keyring_secrets(&mut self) {
+        if !self.use_keyring {
+            return;
+        }
+        let provider = self.provider.clone();
+        let fallbacks = self.fallback_providers.clone();
+        let keys = [
+            ("cerebras", "CEREBRAS_API_KEY", CEREBRAS_KEYRING_USER, &mut self.cerebras_api_key),
+            ("openai", "OPENAI_API_KEY", OPENAI_KEYRING_USER, &mut self.openai_api_key),
+            ("anthropic", "ANTHROPIC_API_KEY", ANTHROPIC_KEYRING_USER, &mut self.anthropic_api_key),
+            ("groq", "GROQ_API_KEY", GROQ_KEYRING_USER, &mut self.groq_api_key),
+            ("gemini", "GEMINI_API_KEY", GEMINI_KEYRING_USER, &mut self.gemini_api_key),
+            ("mistral", "MISTRAL_API_KEY", MISTRAL_KEYRING_USER, &mut self.mistral_api_key),
+        ];
+        for (key_provider, env, user, key) in keys {
+            let wanted = provider.is_empty() || provider == key_provider || fallbacks.iter().any(|f| f == key_provider);
+            if !wanted || env_var(env).is_some() || key.as_ref().is_some_and(|k| !k.is_empty()) {
+                continue;
+            }
+            match keyring_get(user) {
+                Ok(secret) => *key = Some(secret),
+                Err(e) => api::warn(&format!("⚠️  Could not read API key from the system keyring: {}", e)),
+            }
+        }
+    }
+
+    // Moves keys into the keyring and blanks them in the copy written to disk.
+    // If the keyring is unavailable the key stays in plaintext.
+    fn store_keyring_secrets(&mut self) {
+        let keys = [
+            (CEREBRAS_KEYRING_USER, &mut self.cerebras_api_key),
+            (OPENAI_KEYRING_USER, &mut self.openai_api_key),
+            (ANTHROPIC_KEYRING_USER, &mut self.anthropic_api_key),
+            (GROQ_KEYRING_USER, &mut self.groq_api_key),
+            (GEMINI_KEYRING_USER, &mut self.gemini_api_key),
+            (MISTRAL_KEYRING_USER, &mut self.mistral_api_key),
+        ];
+        for (user, key) in keys {
+            if let Some(secret) = key.as_ref().filter(|k| !k.is_empty()) {
+                match keyring_set(user, secret) {
+                    Ok(()) => *key = None,
+                    Err(e) => eprintln!("{}", format!("⚠️  System keyring unavailable ({}), storing the API key in settings.json instead.", e).yellow()),
+                }
+            }
+        }
+    }
+
     pub async fn interactive_setup() -> Result<Settings, String> {
         println!("{}", "🔧 Tella Configuration Setup".bold().cyan());
         println!("{}", "━".repeat(50));
         println!();
 
-        // Provider selection
         println!("{}", "Which model provider would you like to use?".bold());
         println!();
+        Self::print_provider_menu();
+        let choice = Self::read_provider_choice()?;
+
+        // Held until the new settings are written. A read-only directory can't
+        // be locked or written; saving then offers ~/.tella instead.
+        let settings_dir = Self::get_settings_path()?;
+        let lock = match SettingsLock::acquire(&settings_dir) {
+            Ok(lock) => Some(lock),
+            Err((_, e)) if is_read_only(&e) => None,
+            Err((what, e)) => return Err(Self::save_error(&settings_dir, what, &e)),
+        };
+
+        // Setup starts from scratch, but the catalog URL is set by admins and must survive it
+        let catalog_url = Self::get_settings_file()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str::<Settings>(&content).ok())
+            .and_then(|existing| existing.model_catalog_url);
+
+        let mut settings = Self::setup_provider(&choice, catalog_url.as_deref()).await?;
+        settings.output_settings = Self::setup_output_settings()?;
+        Self::setup_fallbacks(&mut settings, catalog_url.as_deref()).await?;
+        settings.model_catalog_url = catalog_url;
+
+        let settings_file = settings.save(lock.as_ref())?;
+
+        println!();
+        println!("{}", "✅ Settings saved successfully!".green());
+        println!("{}", format!("Settings location: {}", settings_file.display()).dimmed());
+        println!();
+
+        Ok(settings)
+    }
+
+    fn print_provider_menu() {
         println!("  {} Ollama (Local, fully offline, free)", "1.".cyan());
         println!("  {} Cerebras (Cloud-based, requires API key)", "2.".cyan());
+        println!("  {} OpenAI (Cloud-based, requires API key)", "3.".cyan());
+        println!("  {} Anthropic (Cloud-based, requires API key)", "4.".cyan());
+        println!("  {} Groq (Cloud-based, very fast, requires API key)", "5.".cyan());
+        println!("  {} Google Gemini (Cloud-based, requires API key)", "6.".cyan());
+        println!("  {} Mistral (Cloud-based, requires API key)", "7.".cyan());
+        println!("  {} LM Studio (Local, OpenAI-compatible server, free)", "8.".cyan());
         println!();
+    }
 
-        print!("{} ", "Choose (1 or 2):".bold());
+    fn read_provider_choice() -> Result<String, String> {
+        print!("{} ", "Choose (1-8):".bold());
         io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
 
         let mut choice = String::new();
         io::stdin()
             .read_line(&mut choice)
             .map_err(|e| format!("Failed to read input: {}", e))?;
+        Ok(choice.trim().to_string())
+    }
 
-        let choice = choice.trim();
+    async fn setup_provider(choice: &str, catalog_url: Option<&str>) -> Result<Settings, String> {
+        match choice {
+            "1" => Self::setup_ollama().await,
+            "2" => {
+                let models = Self::catalog_models(catalog_url, CEREBRAS_MODELS, |c| c.cerebras).await;
+                Self::setup_cerebras(&models)
+            }
+            "3" => {
+                let models = Self::catalog_models(catalog_url, OPENAI_MODELS, |c| c.openai).await;
+                Self::setup_openai(&models)
+            }
+            "4" => {
+                let models = Self::catalog_models(catalog_url, ANTHROPIC_MODELS, |c| c.anthropic).await;
+                Self::setup_anthropic(&models)
+            }
+            "5" => {
+                let models = Self::catalog_models(catalog_url, GROQ_MODELS, |c| c.groq).await;
+                Self::setup_groq(&models)
+            }
+            "6" => {
+                let models = Self::catalog_models(catalog_url, GEMINI_MODELS, |c| c.gemini).await;
+                Self::setup_gemini(&models)
+            }
+            "7" => {
+                let models = Self::catalog_models(catalog_url, MISTRAL_MODELS, |c| c.mistral).await;
+                Self::setup_mistral(&models)
+            }
+            "8" => Self::setup_lmstudio().await,
+            _ => Err("Invalid choice. Please enter a number from 1 to 8.".to_string()),
+        }
+    }
 
-        let settings = match choice {
-            "1" => Self::setup_ollama().await?,
-            "2" => Self::setup_cerebras()?,
-            _ => return Err("Invalid choice. Please enter 1 or 2.".to_string()),
-        };
+    // Providers to try when the primary can't be reached. Each goes through its
+    // own setup, so it has its own credentials and model.
+    async fn setup_fallbacks(settings: &mut Settings, catalog_url: Option<&str>) -> Result<(), String> {
+        loop {
+            println!();
+            let question = if settings.fallback_providers.is_empty() {
+                format!("Add a fallback provider for when {} can't be reached? (y/N):", settings.provider)
+            } else {
+                "Add another fallback provider? (y/N):".to_string()
+            };
+            print!("{} ", question.bold());
+            io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
 
-        settings.save()?;
+            let mut answer = String::new();
+            io::stdin()
+                .read_line(&mut answer)
+                .map_err(|e| format!("Failed to read input: {}", e))?;
+            if !answer.trim().eq_ignore_ascii_case("y") && !answer.trim().eq_ignore_ascii_case("yes") {
+                return Ok(());
+            }
 
-        println!();
-        println!("{}", "✅ Settings saved successfully!".green());
-        println!("{}", format!("Settings location: {}", Self::get_settings_file().display()).dimmed());
-        println!();
+            println!();
+            println!("{}", "Which provider should be tried next?".bold());
+            println!();
+            Self::print_provider_menu();
+            let choice = Self::read_provider_choice()?;
 
-        Ok(settings)
+            // Checked before its setup, which would otherwise be answered for nothing
+            let chosen = choice.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| MENU_PROVIDERS.get(i));
+            if let Some(provider) = chosen.filter(|p| **p == settings.provider || settings.fallback_providers.iter().any(|f| f == *p)) {
+                println!("{}", format!("{} is already in the chain.", provider).yellow());
+                continue;
+            }
+            let fallback = Self::setup_provider(&choice, catalog_url).await?;
+            settings.add_fallback(fallback);
+        }
+    }
+
+    // Takes the provider's credentials and model from a fresh setup. A key the
+    // user wanted in the keyring must not end up in settings.json.
+    fn add_fallback(&mut self, fallback: Settings) {
+        match fallback.provider.as_str() {
+            "ollama" => {
+                self.ollama_base_url = fallback.ollama_base_url;
+                self.ollama_model = fallback.ollama_model;
+            }
+            "cerebras" => {
+                self.cerebras_api_key = fallback.cerebras_api_key;
+                self.cerebras_model = fallback.cerebras_model;
+            }
+            "openai" => {
+                self.openai_api_key = fallback.openai_api_key;
+                self.openai_model = fallback.openai_model;
+            }
+            "anthropic" => {
+                self.anthropic_api_key = fallback.anthropic_api_key;
+                self.anthropic_model = fallback.anthropic_model;
+            }
+            "groq" => {
+                self.groq_api_key = fallback.groq_api_key;
+                self.groq_model = fallback.groq_model;
+            }
+            "gemini" => {
+                self.gemini_api_key = fallback.gemini_api_key;
+                self.gemini_model = fallback.gemini_model;
+            }
+            "mistral" => {
+                self.mistral_api_key = fallback.mistral_api_key;
+                self.mistral_model = fallback.mistral_model;
+            }
+            "lmstudio" => {
+                self.lmstudio_base_url = fallback.lmstudio_base_url;
+                self.lmstudio_model = fallback.lmstudio_model;
+            }
+            _ => return,
+        }
+        self.use_keyring |= fallback.use_keyring;
+        self.fallback_providers.push(fallback.provider);
     }
 
     async fn setup_ollama() -> Result<Settings, String> {
@@ -198,6 +1175,85 @@ impl Settings {
             print!("{} ", "Select model number or enter custom name:".bold());
         }
 
+        let ollama_model = Self::read_local_model_choice(&available_models, |model| {
+            format!("⚠️  '{}' isn't installed; pull it with 'ollama pull {}'.", model, model)
+        })?;
+
+        Ok(Settings {
+            provider: "ollama".to_string(),
+            ollama_model: Some(ollama_model),
+            ollama_base_url: Some(base_url),
+            cerebras_api_key: None,
+            ..Default::default()
+        })
+    }
+
+    async fn setup_lmstudio() -> Result<Settings, String> {
+        println!();
+        println!("{}", "🎯 LM Studio Setup".bold().cyan());
+        println!("{}", "━".repeat(50));
+        println!();
+        println!("{}", "Start LM Studio's local server and load a model before querying; tella can't load one for you.".yellow());
+        println!("{}", format!("Default URL: {}", DEFAULT_LMSTUDIO_URL).dimmed());
+        println!();
+
+        print!("{} ", "Enter LM Studio base URL (press Enter for default):".bold());
+        io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
+
+        let mut base_url = String::new();
+        io::stdin()
+            .read_line(&mut base_url)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+
+        let base_url = match base_url.trim() {
+            "" => DEFAULT_LMSTUDIO_URL.to_string(),
+            url => url.trim_end_matches('/').to_string(),
+        };
+
+        println!();
+        println!("{}", "Fetching models loaded in LM Studio...".cyan());
+
+        let available_models = match Self::fetch_lmstudio_models(&base_url).await {
+            Ok(models) => {
+                println!("{}", format!("✅ Found {} models", models.len()).green());
+                models
+            }
+            Err(e) => {
+                eprintln!("{}", format!("⚠️  Could not fetch models: {}", e).yellow());
+                eprintln!("{}", "You may need to start the LM Studio server first.".yellow());
+                vec![]
+            }
+        };
+
+        println!();
+        if available_models.is_empty() {
+            println!("{}", "No models loaded. Load one in LM Studio, or enter its identifier now.".yellow());
+            println!();
+            print!("{} ", "Enter LM Studio model name manually:".bold());
+        } else {
+            println!("{}", "Available models:".bold());
+            for (i, model) in available_models.iter().enumerate() {
+                println!("  {}) {}", i + 1, model);
+            }
+            println!();
+            print!("{} ", "Select model number or enter custom name:".bold());
+        }
+
+        let lmstudio_model = Self::read_local_model_choice(&available_models, |model| {
+            format!("⚠️  '{}' isn't loaded; load it in LM Studio before asking.", model)
+        })?;
+
+        Ok(Settings {
+            provider: "lmstudio".to_string(),
+            lmstudio_model: Some(lmstudio_model),
+            lmstudio_base_url: Some(base_url),
+            ..Default::default()
+        })
+    }
+
+    // Reads a model number or name for a local server; a name it doesn't list
+    // is kept (after offering the closest match) with `missing` as a warning
+    fn read_local_model_choice(available_models: &[String], missing: impl Fn(&str) -> String) -> Result<String, String> {
         io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
 
         let mut model_choice = String::new();
@@ -207,43 +1263,117 @@ impl Settings {
 
         let model_choice = model_choice.trim();
 
-        let ollama_model = if let Ok(idx) = model_choice.parse::<usize>() {
+        let model = if let Ok(idx) = model_choice.parse::<usize>() {
             if idx > 0 && idx <= available_models.len() {
                 available_models[idx - 1].clone()
             } else {
                 return Err("Invalid selection.".to_string());
             }
-        } else {
+        } else if available_models.is_empty() || available_models.iter().any(|m| m == model_choice) {
             model_choice.to_string()
+        } else {
+            match closest_model(model_choice, available_models) {
+                Some(suggestion) if Self::confirm_suggested_model(suggestion)? => suggestion.to_string(),
+                _ => {
+                    println!("{}", missing(model_choice).yellow());
+                    model_choice.to_string()
+                }
+            }
         };
 
-        if ollama_model.is_empty() {
+        if model.is_empty() {
             return Err("Model name cannot be empty".to_string());
         }
+        Ok(model)
+    }
+
+    fn setup_cerebras(models: &[String]) -> Result<Settings, String> {
+        let (api_key, use_keyring, model) = Self::setup_cloud_provider("Cerebras", "https://console.cerebras.ai/", models)?;
 
         Ok(Settings {
-            provider: "ollama".to_string(),
-            ollama_model: Some(ollama_model),
-            ollama_base_url: Some(base_url),
-            cerebras_api_key: None,
-            output_settings: Self::setup_output_settings()?,
+            provider: "cerebras".to_string(),
+            cerebras_api_key: Some(api_key),
+            cerebras_model: Some(model),
+            use_keyring,
+            ..Default::default()
+        })
+    }
+
+    fn setup_openai(models: &[String]) -> Result<Settings, String> {
+        let (api_key, use_keyring, model) = Self::setup_cloud_provider("OpenAI", "https://platform.openai.com/api-keys", models)?;
+
+        Ok(Settings {
+            provider: "openai".to_string(),
+            openai_api_key: Some(api_key),
+            openai_model: Some(model),
+            use_keyring,
+            ..Default::default()
+        })
+    }
+
+    fn setup_anthropic(models: &[String]) -> Result<Settings, String> {
+        let (api_key, use_keyring, model) = Self::setup_cloud_provider("Anthropic", "https://console.anthropic.com/settings/keys", models)?;
+
+        Ok(Settings {
+            provider: "anthropic".to_string(),
+            anthropic_api_key: Some(api_key),
+            anthropic_model: Some(model),
+            use_keyring,
+            ..Default::default()
+        })
+    }
+
+    fn setup_groq(models: &[String]) -> Result<Settings, String> {
+        let (api_key, use_keyring, model) = Self::setup_cloud_provider("Groq", "https://console.groq.com/keys", models)?;
+
+        Ok(Settings {
+            provider: "groq".to_string(),
+            groq_api_key: Some(api_key),
+            groq_model: Some(model),
+            use_keyring,
+            ..Default::default()
+        })
+    }
+
+    fn setup_gemini(models: &[String]) -> Result<Settings, String> {
+        let (api_key, use_keyring, model) = Self::setup_cloud_provider("Gemini", "https://aistudio.google.com/apikey", models)?;
+
+        Ok(Settings {
+            provider: "gemini".to_string(),
+            gemini_api_key: Some(api_key),
+            gemini_model: Some(model),
+            use_keyring,
+            ..Default::default()
         })
     }
 
-    fn setup_cerebras() -> Result<Settings, String> {
+    fn setup_mistral(models: &[String]) -> Result<Settings, String> {
+        let (api_key, use_keyring, model) = Self::setup_cloud_provider("Mistral", "https://console.mistral.ai/api-keys", models)?;
+
+        Ok(Settings {
+            provider: "mistral".to_string(),
+            mistral_api_key: Some(api_key),
+            mistral_model: Some(model),
+            use_keyring,
+            ..Default::default()
+        })
+    }
+
+    // Asks for the API key, where to keep it and the model; returns them in that order
+    fn setup_cloud_provider(name: &str, key_url: &str, models: &[String]) -> Result<(String, bool, String), String> {
         println!();
-        println!("{}", "🎯 Cerebras Setup".bold().cyan());
+        println!("{}", format!("🎯 {} Setup", name).bold().cyan());
         println!("{}", "━".repeat(50));
         println!();
-        println!("{}", "Get your API key from: https://console.cerebras.ai/".yellow());
+        println!("{}", format!("Get your API key from: {}", key_url).yellow());
         println!();
         println!("{}", "Available models:".bold());
-        for model in CEREBRAS_MODELS {
+        for model in models {
             println!("  • {}", model);
         }
         println!();
 
-        print!("{} ", "Enter your Cerebras API key:".bold());
+        print!("{} ", format!("Enter your {} API key:", name).bold());
         io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
 
         let mut api_key = String::new();
@@ -257,14 +1387,22 @@ impl Settings {
             return Err("API key cannot be empty".to_string());
         }
 
+        print!("{} ", "Store the API key in the system keyring instead of settings.json? (Y/n):".bold());
+        io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
+        let mut keyring_choice = String::new();
+        io::stdin()
+            .read_line(&mut keyring_choice)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+        let use_keyring = !keyring_choice.trim().eq_ignore_ascii_case("n");
+
         // Ask which model to use
         println!();
-        println!("{}", "Which Cerebras model would you like to use?".bold());
-        for (i, model) in CEREBRAS_MODELS.iter().enumerate() {
+        println!("{}", format!("Which {} model would you like to use?", name).bold());
+        for (i, model) in models.iter().enumerate() {
             println!("  {}) {}", i + 1, model);
         }
         println!();
-        print!("{} ", "Select model number:".bold());
+        print!("{} ", "Select model number or enter its name:".bold());
         io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
 
         let mut model_choice = String::new();
@@ -272,22 +1410,84 @@ impl Settings {
             .read_line(&mut model_choice)
             .map_err(|e| format!("Failed to read input: {}", e))?;
 
-        let model_idx = model_choice
-            .trim()
-            .parse::<usize>()
-            .map_err(|_| "Invalid selection.".to_string())?;
+        let model_choice = model_choice.trim();
+        let model = if let Ok(idx) = model_choice.parse::<usize>() {
+            if idx == 0 || idx > models.len() {
+                return Err("Invalid selection.".to_string());
+            }
+            models[idx - 1].clone()
+        } else if models.iter().any(|m| m == model_choice) {
+            model_choice.to_string()
+        } else {
+            // Unlike Ollama there's nothing to pull, so an unknown name is always a mistake
+            match closest_model(model_choice, models) {
+                Some(suggestion) if Self::confirm_suggested_model(suggestion)? => suggestion.to_string(),
+                _ => return Err(format!("Unknown {} model: {}", name, model_choice)),
+            }
+        };
+
+        Ok((api_key, use_keyring, model))
+    }
+
+    fn confirm_suggested_model(suggestion: &str) -> Result<bool, String> {
+        print!("{} ", format!("Did you mean '{}'? (Y/n):", suggestion).bold());
+        io::stdout().flush().map_err(|e| format!("IO error: {}", e))?;
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(|e| format!("Failed to read input: {}", e))?;
+        Ok(!answer.trim().eq_ignore_ascii_case("n"))
+    }
+
+    /// Models to offer for a cloud provider: its list in the team catalog when
+    /// configured (cached in model_catalog.json for when it can't be fetched),
+    /// else the built-in list.
+    async fn catalog_models(catalog_url: Option<&str>, builtin: &[&str], models: fn(ModelCatalog) -> Vec<String>) -> Vec<String> {
+        let builtin = || builtin.iter().map(|m| m.to_string()).collect();
+        let Some(url) = catalog_url else {
+            return builtin();
+        };
+        let cache_file = Self::get_settings_path().ok().map(|dir| dir.join("model_catalog.json"));
+
+        let catalog = match Self::fetch_model_catalog(url).await {
+            Ok(catalog) => {
+                if let (Some(path), Ok(content)) = (&cache_file, serde_json::to_string(&catalog)) {
+                    let _ = fs::write(path, content);
+                }
+                Some(catalog)
+            }
+            Err(e) => {
+                println!("{}", format!("⚠️  Could not fetch the model catalog: {}", e).yellow());
+                cache_file
+                    .and_then(|path| fs::read_to_string(path).ok())
+                    .and_then(|content| serde_json::from_str::<ModelCatalog>(&content).ok())
+            }
+        };
 
-        if model_idx == 0 || model_idx > CEREBRAS_MODELS.len() {
-            return Err("Invalid selection.".to_string());
+        match catalog.map(models) {
+            Some(models) if !models.is_empty() => models,
+            _ => builtin(),
         }
+    }
 
-        Ok(Settings {
-            provider: "cerebras".to_string(),
-            cerebras_api_key: Some(api_key),
-            ollama_model: Some(CEREBRAS_MODELS[model_idx - 1].to_string()),
-            ollama_base_url: None,
-            output_settings: Self::setup_output_settings()?,
-        })
+    async fn fetch_model_catalog(url: &str) -> Result<ModelCatalog, String> {
+        if api::is_offline() {
+            return Err("offline mode".to_string());
+        }
+
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            reqwest::Client::new().get(url).send(),
+        )
+        .await
+        .map_err(|_| "request timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+
+        response.json().await.map_err(|e| format!("invalid catalog: {}", e))
     }
 
     fn setup_output_settings() -> Result<OutputSettings, String> {
@@ -340,7 +1540,42 @@ impl Settings {
         Ok(settings)
     }
 
+    /// Models LM Studio has loaded, from its OpenAI-style `/v1/models`.
+    async fn fetch_lmstudio_models(base_url: &str) -> Result<Vec<String>, String> {
+        if api::is_offline() {
+            return Err("Offline mode (--offline): not listing LM Studio models.".to_string());
+        }
+
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            reqwest::Client::new().get(format!("{}/v1/models", base_url)).send(),
+        )
+        .await
+        .map_err(|_| "request timed out".to_string())?
+        .map_err(|e| format!("Failed to connect to LM Studio: {}", e))?;
+
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        Ok(json
+            .get("data")
+            .and_then(|d| d.as_array())
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|m| m.get("id").and_then(|id| id.as_str()).map(|id| id.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
     async fn fetch_ollama_models(base_url: &str) -> Result<Vec<String>, String> {
+        if api::is_offline() {
+            return Err("Offline mode (--offline): not listing Ollama models.".to_string());
+        }
+
         let url = format!("{}/api/tags", base_url);
         let client = reqwest::Client::new();
 
@@ -376,3 +1611,82 @@ impl Settings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tella-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn settings_path_without_config_dir_uses_home() {
+        let path = Settings::resolve_settings_path(None, Some(PathBuf::from("/home/me"))).unwrap();
+        assert_eq!(path, PathBuf::from("/home/me/.tella"));
+    }
+
+    #[test]
+    fn settings_path_without_config_dir_or_home_is_an_error() {
+        let error = Settings::resolve_settings_path(None, None).unwrap_err();
+        assert!(error.contains("$HOME/.tella"), "{}", error);
+    }
+
+    #[test]
+    fn settings_path_prefers_the_config_dir() {
+        let dir = test_dir("config-dir");
+        let path = Settings::resolve_settings_path(Some(dir.join("config")), Some(dir.join("home"))).unwrap();
+        assert_eq!(path, dir.join("config").join("tella"));
+
+        // Unless only ~/.tella has settings, which is where setup saves when the config dir is read-only
+        fs::create_dir_all(dir.join("home").join(".tella")).unwrap();
+        fs::write(dir.join("home").join(".tella").join("settings.json"), "{}").unwrap();
+        let path = Settings::resolve_settings_path(Some(dir.join("config")), Some(dir.join("home"))).unwrap();
+        assert_eq!(path, dir.join("home").join(".tella"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_keeps_the_old_settings() {
+        let dir = test_dir("failed-write");
+        fs::write(dir.join("settings.json"), "{\"provider\": \"ollama\"}").unwrap();
+        // A directory where the temp file goes makes writing it fail
+        fs::create_dir(dir.join(format!("settings.json.{}.tmp", std::process::id()))).unwrap();
+
+        assert!(Settings::default().save_in(&dir).is_err());
+        assert_eq!(fs::read_to_string(dir.join("settings.json")).unwrap(), "{\"provider\": \"ollama\"}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_rename_leaves_no_partial_file() {
+        let dir = test_dir("failed-rename");
+        // settings.json as a non-empty directory can't be replaced by the rename
+        fs::create_dir_all(dir.join("settings.json").join("keep")).unwrap();
+
+        assert!(Settings::default().save_in(&dir).is_err());
+        let leftovers: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty(), "temp files left behind: {:?}", leftovers);
+        assert!(dir.join("settings.json").is_dir());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn save_replaces_the_file_whole() {
+        let dir = test_dir("save");
+        fs::write(dir.join("settings.json"), "x".repeat(10_000)).unwrap();
+
+        let settings = Settings { provider: "ollama".to_string(), ..Default::default() };
+        settings.save_in(&dir).unwrap();
+        let saved: Settings = serde_json::from_str(&fs::read_to_string(dir.join("settings.json")).unwrap()).unwrap();
+        assert_eq!(saved.provider, "ollama");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
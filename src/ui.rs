@@ -1,8 +1,8 @@
 use colored::*;
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent},
-    terminal::{enable_raw_mode, disable_raw_mode},
-    cursor::{Hide, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    terminal::{enable_raw_mode, disable_raw_mode, Clear, ClearType},
+    cursor::{Hide, MoveToColumn, MoveToPreviousLine, Show},
     execute,
 };
 use std::io::{self, Write};
@@ -11,12 +11,14 @@ use std::time::Duration;
 
 pub struct MenuSelector {
     options: Vec<(String, String)>,
+    hotkeys: Vec<(char, String)>,
 }
 
 impl MenuSelector {
     pub fn new() -> Self {
         MenuSelector {
             options: Vec::new(),
+            hotkeys: Vec::new(),
         }
     }
 
@@ -25,13 +27,56 @@ impl MenuSelector {
         self
     }
 
+    /// A single-key action shown as a hint after the options. Pressing it
+    /// returns from `show()` like an option; see `hotkey`.
+    pub fn add_hotkey(mut self, key: char, title: &str) -> Self {
+        self.hotkeys.push((key, title.to_string()));
+        self
+    }
+
+    /// The hotkey behind an index returned by `show()`, if it was one.
+    /// Hotkeys come after the options and the cancel index.
+    pub fn hotkey(&self, selected: usize) -> Option<char> {
+        let index = selected.checked_sub(self.options.len() + 1)?;
+        self.hotkeys.get(index).map(|(key, _)| *key)
+    }
+
+    fn hotkey_index(&self, key: char) -> Option<usize> {
+        self.hotkeys
+            .iter()
+            .position(|(hotkey, _)| hotkey.eq_ignore_ascii_case(&key))
+            .map(|i| self.options.len() + 1 + i)
+    }
+
+    fn hotkey_hint(&self) -> String {
+        self.hotkeys
+            .iter()
+            .map(|(key, title)| format!("  {}: {}", key, title))
+            .collect()
+    }
+
     pub fn show(&self) -> io::Result<usize> {
-        let mut selected = 0;
-        let option_count = self.options.len();
+        // Minimal terminals (some Docker/CI shells) can't do raw mode
+        if enable_raw_mode().is_err() {
+            return self.show_line_prompt();
+        }
 
-        enable_raw_mode()?;
         let mut stdout = io::stdout();
-        execute!(stdout, Hide)?;
+        let cursor_hidden = execute!(stdout, Hide).is_ok();
+
+        let result = self.select_loop();
+
+        if cursor_hidden {
+            execute!(stdout, Show).ok();
+        }
+        disable_raw_mode()?;
+        println!();
+        result
+    }
+
+    fn select_loop(&self) -> io::Result<usize> {
+        let mut selected = 0;
+        let option_count = self.options.len();
 
         thread::sleep(Duration::from_millis(200));
 
@@ -51,6 +96,7 @@ impl MenuSelector {
                     print!("{}", title.dimmed());
                 }
             }
+            print!("{}", self.hotkey_hint().dimmed());
             io::stdout().flush()?;
 
             if let Ok(true) = event::poll(Duration::from_millis(50)) {
@@ -76,14 +122,11 @@ impl MenuSelector {
                         KeyCode::Esc => {
                             break Ok(option_count);
                         }
-                        KeyCode::Char('1') if option_count >= 1 => {
-                            break Ok(0);
-                        }
-                        KeyCode::Char('2') if option_count >= 2 => {
-                            break Ok(1);
+                        KeyCode::Char(c @ '1'..='9') if (c as usize - '1' as usize) < option_count => {
+                            break Ok(c as usize - '1' as usize);
                         }
-                        KeyCode::Char('3') if option_count >= 3 => {
-                            break Ok(2);
+                        KeyCode::Char(c) if self.hotkey_index(c).is_some() => {
+                            break Ok(self.hotkey_index(c).unwrap_or(option_count));
                         }
                         _ => {}
                     }
@@ -91,11 +134,42 @@ impl MenuSelector {
             }
         };
 
-        execute!(stdout, Show)?;
-        disable_raw_mode()?;
-        println!();
         result
     }
+
+    // Numbered fallback; EOF or an empty answer cancels like Esc does
+    fn show_line_prompt(&self) -> io::Result<usize> {
+        let option_count = self.options.len();
+
+        for (i, (title, _)) in self.options.iter().enumerate() {
+            println!("  {} {}", format!("{})", i + 1).cyan(), title);
+        }
+        for (key, title) in &self.hotkeys {
+            println!("  {} {}", format!("{})", key).cyan(), title);
+        }
+
+        loop {
+            print!("{} ", format!("Choose (1-{}):", option_count).bold());
+            io::stdout().flush()?;
+
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input)? == 0 || input.trim().is_empty() {
+                return Ok(option_count);
+            }
+
+            let mut chars = input.trim().chars();
+            if let (Some(key), None) = (chars.next(), chars.next()) {
+                if let Some(index) = self.hotkey_index(key) {
+                    return Ok(index);
+                }
+            }
+
+            match input.trim().parse::<usize>() {
+                Ok(choice) if choice >= 1 && choice <= option_count => return Ok(choice - 1),
+                _ => println!("{}", "Invalid choice.".yellow()),
+            }
+        }
+    }
 }
 
 impl Default for MenuSelector {
@@ -104,3 +178,317 @@ impl Default for MenuSelector {
     }
 }
 
+const PICKER_ROWS: usize = 10;
+
+/// Vertical list with filter-as-you-type: typing narrows the list, arrows move,
+/// Enter picks, Esc cancels. Returns the index into the original items.
+pub struct ListPicker {
+    items: Vec<String>,
+}
+
+impl ListPicker {
+    pub fn new(items: Vec<String>) -> Self {
+        ListPicker { items }
+    }
+
+    pub fn show(&self) -> io::Result<Option<usize>> {
+        if self.items.is_empty() {
+            return Ok(None);
+        }
+        if enable_raw_mode().is_err() {
+            return self.show_line_prompt();
+        }
+
+        let mut stdout = io::stdout();
+        let cursor_hidden = execute!(stdout, Hide).is_ok();
+
+        let result = self.select_loop();
+
+        if cursor_hidden {
+            execute!(stdout, Show).ok();
+        }
+        disable_raw_mode()?;
+        result
+    }
+
+    fn select_loop(&self) -> io::Result<Option<usize>> {
+        let mut stdout = io::stdout();
+        let mut filter = String::new();
+        let mut selected = 0;
+        let mut drawn = 0;
+
+        loop {
+            let needle = filter.to_lowercase();
+            let matches: Vec<usize> = (0..self.items.len())
+                .filter(|&i| self.items[i].to_lowercase().contains(&needle))
+                .collect();
+            selected = selected.min(matches.len().saturating_sub(1));
+
+            // Redraw in place: back to the first line we drew, clear, draw again
+            if drawn > 0 {
+                execute!(stdout, MoveToPreviousLine(drawn as u16))?;
+            }
+            execute!(stdout, Clear(ClearType::FromCursorDown))?;
+
+            let first = selected.saturating_sub(PICKER_ROWS - 1);
+            print!("{} {}\r\n", "Filter:".bold(), filter);
+            for (row, &index) in matches.iter().enumerate().skip(first).take(PICKER_ROWS) {
+                if row == selected {
+                    print!("{} {}\r\n", ">".green().bold(), self.items[index].green().bold());
+                } else {
+                    print!("  {}\r\n", self.items[index].dimmed());
+                }
+            }
+            if matches.is_empty() {
+                print!("  {}\r\n", "No matches".yellow());
+            }
+            let shown = matches.len().saturating_sub(first).clamp(1, PICKER_ROWS);
+            print!("{}", format!("{}/{} · ↑↓ move · Enter select · Esc cancel", matches.len(), self.items.len()).dimmed());
+            stdout.flush()?;
+            drawn = shown + 1;
+
+            if let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? {
+                if kind == KeyEventKind::Release {
+                    continue;
+                }
+                // Raw mode delivers Ctrl-C as a key rather than SIGINT; it cancels like Esc
+                if code == KeyCode::Esc || (code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL)) {
+                    print!("\r\n");
+                    return Ok(None);
+                }
+                match code {
+                    KeyCode::Up => selected = selected.saturating_sub(1),
+                    KeyCode::Down if selected + 1 < matches.len() => selected += 1,
+                    KeyCode::Enter if !matches.is_empty() => {
+                        print!("\r\n");
+                        return Ok(Some(matches[selected]));
+                    }
+                    KeyCode::Backspace => {
+                        filter.pop();
+                        selected = 0;
+                    }
+                    KeyCode::Char(c) => {
+                        filter.push(c);
+                        selected = 0;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // Numbered fallback without raw mode; no live filtering
+    fn show_line_prompt(&self) -> io::Result<Option<usize>> {
+        for (i, item) in self.items.iter().enumerate() {
+            println!("  {} {}", format!("{})", i + 1).cyan(), item);
+        }
+
+        loop {
+            print!("{} ", format!("Choose (1-{}):", self.items.len()).bold());
+            io::stdout().flush()?;
+
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input)? == 0 || input.trim().is_empty() {
+                return Ok(None);
+            }
+
+            match input.trim().parse::<usize>() {
+                Ok(choice) if choice >= 1 && choice <= self.items.len() => return Ok(Some(choice - 1)),
+                _ => println!("{}", "Invalid choice.".yellow()),
+            }
+        }
+    }
+}
+
+const SHELL_OPERATORS: &[&str] = &["&&", "||", ">>", "2>", "|", ";", ">", "<", "&"];
+
+/// Colors a shell command for display: programs bold, flags cyan, quoted strings
+/// and paths green, operators magenta. Whitespace is kept exactly as given.
+pub fn highlight_command(command: &str) -> String {
+    let mut out = String::new();
+    let mut rest = command;
+    let mut command_position = true;
+    let mut redirect_target = false;
+
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_whitespace() {
+            rest.find(|c: char| !c.is_whitespace()).unwrap_or(rest.len())
+        } else if let Some(op) = SHELL_OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            op.len()
+        } else if c == '\'' || c == '"' {
+            // Through the closing quote, or to the end if it's unbalanced
+            rest[1..].find(c).map_or(rest.len(), |end| end + 2)
+        } else {
+            rest.find(|c: char| c.is_whitespace() || "|;&<>".contains(c)).unwrap_or(rest.len())
+        };
+        let (token, tail) = rest.split_at(len);
+        rest = tail;
+
+        let colored = if token.trim().is_empty() {
+            token.normal()
+        } else if SHELL_OPERATORS.contains(&token) {
+            // After a redirection comes a file, after anything else a new command
+            redirect_target = [">", ">>", "2>", "<"].contains(&token);
+            command_position = !redirect_target;
+            out.push_str(&token.magenta().to_string());
+            continue;
+        } else if redirect_target {
+            redirect_target = false;
+            token.green()
+        } else if command_position {
+            // sudo and VAR=value prefixes keep the next word in command position
+            command_position = token == "sudo" || token.contains('=');
+            token.bold().yellow()
+        } else if token.starts_with('-') {
+            token.cyan()
+        } else if token.starts_with(['\'', '"', '/', '~', '.']) || token.contains('/') {
+            token.green()
+        } else {
+            token.yellow()
+        };
+        out.push_str(&colored.to_string());
+    }
+
+    out
+}
+
+/// Single-line editor prefilled with `initial`: arrows/Home/End move, Enter
+/// accepts, Esc cancels. Tab on the first word completes it from `complete`
+/// (the common prefix of the candidates; a second Tab lists them).
+pub struct LineEditor<F: Fn(&str) -> Vec<String>> {
+    initial: String,
+    complete: F,
+}
+
+const MAX_LISTED_COMPLETIONS: usize = 8;
+
+impl<F: Fn(&str) -> Vec<String>> LineEditor<F> {
+    pub fn new(initial: &str, complete: F) -> Self {
+        LineEditor {
+            initial: initial.to_string(),
+            complete,
+        }
+    }
+
+    pub fn show(&self) -> io::Result<Option<String>> {
+        if enable_raw_mode().is_err() {
+            return self.show_line_prompt();
+        }
+        let result = self.edit_loop();
+        disable_raw_mode()?;
+        result
+    }
+
+    fn edit_loop(&self) -> io::Result<Option<String>> {
+        let mut stdout = io::stdout();
+        let mut text: Vec<char> = self.initial.chars().collect();
+        let mut cursor = text.len();
+        let mut last_was_tab = false;
+        let mut hint = String::new();
+
+        loop {
+            // Long commands scroll horizontally so the line never wraps
+            let width = crossterm::terminal::size().map_or(80, |(cols, _)| cols as usize).saturating_sub(3).max(10);
+            let start = cursor.saturating_sub(width - 1);
+            let visible: String = text.iter().skip(start).take(width).collect();
+            execute!(stdout, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+            print!("{} {}", ">".cyan().bold(), visible);
+            if !hint.is_empty() {
+                print!("\r\n{}", hint.dimmed());
+                execute!(stdout, MoveToPreviousLine(1))?;
+            }
+            execute!(stdout, MoveToColumn((cursor - start + 2) as u16))?;
+            stdout.flush()?;
+
+            let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+                continue;
+            };
+            if kind == KeyEventKind::Release {
+                continue;
+            }
+            hint.clear();
+            let tab = code == KeyCode::Tab;
+
+            // Raw mode delivers Ctrl-C as a key rather than SIGINT; it cancels like Esc
+            if code == KeyCode::Esc || (code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL)) {
+                execute!(stdout, Clear(ClearType::FromCursorDown))?;
+                print!("\r\n");
+                return Ok(None);
+            }
+            match code {
+                KeyCode::Enter => {
+                    execute!(stdout, MoveToColumn(0), Clear(ClearType::FromCursorDown))?;
+                    print!("{} {}\r\n", ">".cyan().bold(), text.iter().collect::<String>());
+                    return Ok(Some(text.into_iter().collect()));
+                }
+                KeyCode::Left => cursor = cursor.saturating_sub(1),
+                KeyCode::Right => cursor = (cursor + 1).min(text.len()),
+                KeyCode::Home => cursor = 0,
+                KeyCode::End => cursor = text.len(),
+                KeyCode::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    text.remove(cursor);
+                }
+                KeyCode::Delete if cursor < text.len() => {
+                    text.remove(cursor);
+                }
+                KeyCode::Tab => {
+                    let line: String = text.iter().collect();
+                    let word_start = line.len() - line.trim_start().len();
+                    let word_end = line[word_start..].find(char::is_whitespace).map_or(line.len(), |i| word_start + i);
+                    let cursor_byte = text.iter().take(cursor).map(|c| c.len_utf8()).sum::<usize>();
+                    // Only the program name (the first word) is completed
+                    if cursor_byte >= word_start && cursor_byte <= word_end {
+                        let candidates = (self.complete)(&line[word_start..word_end]);
+                        if let Some(common) = common_prefix(&candidates) {
+                            let completed = format!("{}{}", &line[..word_start], common);
+                            let suffix = &line[word_end..];
+                            let add_space = candidates.len() == 1 && !suffix.starts_with(' ');
+                            let new_line = format!("{}{}{}", completed, if add_space { " " } else { "" }, suffix);
+                            cursor = completed.chars().count() + usize::from(add_space);
+                            text = new_line.chars().collect();
+                        }
+                        if candidates.len() > 1 && last_was_tab {
+                            let mut listed = candidates.iter().take(MAX_LISTED_COMPLETIONS).cloned().collect::<Vec<_>>().join("  ");
+                            if candidates.len() > MAX_LISTED_COMPLETIONS {
+                                listed.push_str(&format!("  … {} more", candidates.len() - MAX_LISTED_COMPLETIONS));
+                            }
+                            hint = listed;
+                        }
+                    }
+                }
+                KeyCode::Char(c) => {
+                    text.insert(cursor, c);
+                    cursor += 1;
+                }
+                _ => {}
+            }
+            last_was_tab = tab;
+        }
+    }
+
+    // Without raw mode: show the command and read a replacement; empty keeps it
+    fn show_line_prompt(&self) -> io::Result<Option<String>> {
+        println!("{} {}", "Current:".dimmed(), self.initial);
+        print!("{} ", "New command (Enter keeps it):".bold());
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        let input = input.trim();
+        Ok(Some(if input.is_empty() { self.initial.clone() } else { input.to_string() }))
+    }
+}
+
+fn common_prefix(candidates: &[String]) -> Option<String> {
+    let first = candidates.first()?;
+    let mut prefix = first.clone();
+    for candidate in &candidates[1..] {
+        while !candidate.starts_with(&prefix) {
+            prefix.pop();
+        }
+    }
+    Some(prefix)
+}
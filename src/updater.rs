@@ -1,5 +1,10 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use colored::*;
+use crate::api;
+use crate::clock;
+use crate::settings::Settings;
+use std::fs;
+use std::path::PathBuf;
 
 #[derive(Deserialize, Debug)]
 struct NpmPackageInfo {
@@ -15,19 +20,78 @@ struct DistTags {
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 const PACKAGE_NAME: &str = "tella";
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct UpdateState {
+    last_notified_version: String,
+    last_notified_at: u64,
+}
+
 pub async fn check_for_updates() {
-    match fetch_latest_version().await {
-        Ok(latest_version) => {
-            if should_update(&latest_version) {
-                print_update_notification(&latest_version);
-            }
+    if let Ok(latest_version) = fetch_latest_version().await {
+        if should_update(&latest_version) && !recently_notified(&latest_version) {
+            print_update_notification(&latest_version);
+            record_notification(&latest_version);
         }
-        Err(_) => {
+    }
+}
+
+/// `tella --check-update`: checks right away, ignoring the snooze window.
+pub async fn force_update_check() -> Result<(), String> {
+    let latest_version = fetch_latest_version().await?;
+
+    if should_update(&latest_version) {
+        print_update_notification(&latest_version);
+        record_notification(&latest_version);
+    } else {
+        println!("{}", format!("✓ You're already on the latest version ({})", CURRENT_VERSION).green());
+    }
+
+    Ok(())
+}
+
+fn state_file() -> Result<PathBuf, String> {
+    Ok(Settings::get_settings_path()?.join("update_state.json"))
+}
+
+// The banner is snoozed for update_snooze_days after it was shown for the same version
+fn recently_notified(latest_version: &str) -> bool {
+    let snooze_days = match Settings::load().ok().and_then(|s| s.update_snooze_days) {
+        Some(days) if days > 0 => days,
+        _ => return false,
+    };
+
+    let state: UpdateState = match state_file()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+    {
+        Some(state) => state,
+        None => return false,
+    };
+
+    state.last_notified_version == latest_version
+        && clock::age_secs(state.last_notified_at) < snooze_days * 86_400
+}
+
+fn record_notification(latest_version: &str) {
+    let state = UpdateState {
+        last_notified_version: latest_version.to_string(),
+        last_notified_at: clock::now_secs(),
+    };
+
+    if let (Ok(path), Ok(content)) = (state_file(), serde_json::to_string(&state)) {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
         }
+        let _ = fs::write(path, content);
     }
 }
 
 async fn fetch_latest_version() -> Result<String, String> {
+    if api::is_offline() {
+        return Err("Offline mode (--offline): can't check for updates.".to_string());
+    }
+
     let client = reqwest::Client::new();
     let url = format!("https://registry.npmjs.org/{}", PACKAGE_NAME);
 
@@ -97,39 +161,73 @@ pub async fn perform_upgrade() -> Result<(), String> {
         .cyan()
     );
 
-    #[cfg(target_os = "windows")]
-    {
-        use std::process::Command;
-        Command::new("powershell")
-            .args(&["-Command", install_cmd])
-            .spawn()
-            .map_err(|e| format!("Failed to run upgrade: {}", e))?
-            .wait()
-            .map_err(|e| format!("Upgrade failed: {}", e))?;
+    run_install(install_cmd).await?;
+
+    println!("{}", "✓ Upgrade complete!".green());
+    Ok(())
+}
+
+// Lines of npm's stderr kept for the error message when the install fails
+const ERROR_TAIL_LINES: usize = 10;
+
+/// Runs the install command, streaming its output indented under the progress
+/// line, and fails with the tail of its stderr when it exits non-zero.
+async fn run_install(install_cmd: &str) -> Result<(), String> {
+    use std::process::Stdio;
+    use tokio::io::{AsyncBufReadExt, BufReader};
+    use tokio::process::Command;
+
+    let (shell, flag) = if cfg!(target_os = "windows") { ("powershell", "-Command") } else { ("bash", "-c") };
+    let mut child = Command::new(shell)
+        .args([flag, install_cmd])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run upgrade: {}", e))?;
+
+    // Split on raw newlines and decode each whole line: a multi-byte character is
+    // never cut in half by a read boundary, and stray invalid bytes (which make
+    // `lines()` error out and stop reading) come through as U+FFFD instead
+    let mut stdout = BufReader::new(child.stdout.take().ok_or("Failed to capture upgrade output")?).split(b'\n');
+    let mut stderr = BufReader::new(child.stderr.take().ok_or("Failed to capture upgrade output")?).split(b'\n');
+    let mut error_tail: Vec<String> = Vec::new();
+    let (mut stdout_open, mut stderr_open) = (true, true);
+
+    while stdout_open || stderr_open {
+        tokio::select! {
+            line = stdout.next_segment(), if stdout_open => match line {
+                Ok(Some(line)) => println!("{}", format!("  │ {}", decode_line(&line)).dimmed()),
+                _ => stdout_open = false,
+            },
+            line = stderr.next_segment(), if stderr_open => match line {
+                Ok(Some(line)) => {
+                    let line = decode_line(&line);
+                    println!("{}", format!("  │ {}", line).dimmed());
+                    error_tail.push(line);
+                    if error_tail.len() > ERROR_TAIL_LINES {
+                        error_tail.remove(0);
+                    }
+                }
+                _ => stderr_open = false,
+            },
+        }
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        use std::process::Command;
-        Command::new("bash")
-            .args(&["-c", install_cmd])
-            .spawn()
-            .map_err(|e| format!("Failed to run upgrade: {}", e))?
-            .wait()
-            .map_err(|e| format!("Upgrade failed: {}", e))?;
+    let status = child.wait().await.map_err(|e| format!("Upgrade failed: {}", e))?;
+    if status.success() {
+        return Ok(());
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::process::Command;
-        Command::new("bash")
-            .args(&["-c", install_cmd])
-            .spawn()
-            .map_err(|e| format!("Failed to run upgrade: {}", e))?
-            .wait()
-            .map_err(|e| format!("Upgrade failed: {}", e))?;
+    let code = status.code().map_or("a signal".to_string(), |code| format!("exit code {}", code));
+    let mut message = format!("Upgrade failed ({}) running '{}'", code, install_cmd);
+    if !error_tail.is_empty() {
+        message.push_str(&format!(":\n{}", error_tail.join("\n")));
     }
+    Err(message)
+}
 
-    println!("{}", "✓ Upgrade complete!".green());
-    Ok(())
+fn decode_line(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+    String::from_utf8_lossy(bytes).into_owned()
 }
@@ -0,0 +1,83 @@
+/// Decodes UTF-8 that arrives in chunks. A multi-byte character cut in two by a
+/// chunk boundary is held back until the rest of it arrives, where decoding each
+/// chunk on its own would turn both halves into U+FFFD.
+#[derive(Debug, Default)]
+pub struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// The text completed by `chunk`. Invalid bytes come out as U+FFFD; an
+    /// incomplete character at the end is kept for the next chunk.
+    pub fn push(&mut self, chunk: &[u8]) -> String {
+        self.pending.extend_from_slice(chunk);
+
+        let mut text = String::new();
+        let mut start = 0;
+        while start < self.pending.len() {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    start = self.pending.len();
+                }
+                Err(e) => {
+                    let valid_end = start + e.valid_up_to();
+                    text.push_str(&String::from_utf8_lossy(&self.pending[start..valid_end]));
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            start = valid_end + len;
+                        }
+                        // The chunk ends partway through a character
+                        None => {
+                            start = valid_end;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending.drain(..start);
+        text
+    }
+
+    /// Whatever is still held back, for a stream that ends mid-character.
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn emoji_split_across_chunks() {
+        let bytes = "ok 🚀 done".as_bytes();
+        let split = "ok ".len() + 2;
+
+        let mut decoder = Utf8Decoder::default();
+        let first = decoder.push(&bytes[..split]);
+        let second = decoder.push(&bytes[split..]);
+
+        assert_eq!(first, "ok ");
+        assert_eq!(second, "🚀 done");
+        assert_eq!(decoder.finish(), "");
+    }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        let mut decoder = Utf8Decoder::default();
+        let text: String = "héllo 🚀".bytes().map(|byte| decoder.push(&[byte])).collect();
+        assert_eq!(text, "héllo 🚀");
+    }
+
+    #[test]
+    fn invalid_and_truncated_bytes() {
+        let mut decoder = Utf8Decoder::default();
+        assert_eq!(decoder.push(b"a\xffb\xf0\x9f"), "a\u{FFFD}b");
+        assert_eq!(decoder.finish(), "\u{FFFD}");
+    }
+}